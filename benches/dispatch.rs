@@ -0,0 +1,111 @@
+//!
+//! Compares sink dispatch through the lock-free sink registry with a
+//! baseline reproducing the previous `Mutex<Option<..>>` sink slot.
+//!
+//! The `registry` and `mutex` benchmarks deliver a prepared record to the
+//! sinks without console output, using `impls::sink_impl` and the baseline
+//! respectively, with no sinks and with one sink installed, from a single
+//! thread and from multiple threads. The `log_info!` benchmarks measure
+//! complete log calls with the messages consumed by the sinks.
+//!
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use workflow_log::*;
+
+/// Sink that consumes all messages without producing output.
+struct NullSink;
+
+impl Sink for NullSink {
+    fn write_record(&self, _record : &Record<'_>) -> SinkOutput {
+        SinkOutput::Consume
+    }
+}
+
+/// The sink slot used before the introduction of the sink registry.
+static BASELINE : Mutex<Option<Arc<dyn Sink>>> = Mutex::new(None);
+
+/// Dispatch as performed before the introduction of the sink registry:
+/// every message locks the sink slot, even if no sink is installed.
+fn baseline_impl(record : &Record<'_>) -> SinkOutput {
+    match BASELINE.lock().unwrap().as_ref() {
+        Some(sink) => sink.write_record(record),
+        None => SinkOutput::PassThrough
+    }
+}
+
+fn install(count : usize) {
+    pipe(None);
+    for _ in 0..count {
+        add_sink(Arc::new(NullSink));
+    }
+    *BASELINE.lock().unwrap() = (count > 0).then(|| Arc::new(NullSink) as Arc<dyn Sink>);
+}
+
+/// Runs `f` `iters` times split across `threads` threads and
+/// returns the elapsed wall time.
+fn run_threads(threads : u64, iters : u64, f : fn(u64)) -> Duration {
+    let start = Instant::now();
+    let handles = (0..threads).map(|_| {
+        std::thread::spawn(move || {
+            for i in 0..iters / threads {
+                f(i);
+            }
+        })
+    }).collect::<Vec<_>>();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn registry(i : u64) {
+    let record = Record::new(Level::Info, format_args!("message"));
+    criterion::black_box(impls::sink_impl(criterion::black_box(&record)));
+    criterion::black_box(i);
+}
+
+fn mutex(i : u64) {
+    let record = Record::new(Level::Info, format_args!("message"));
+    criterion::black_box(baseline_impl(criterion::black_box(&record)));
+    criterion::black_box(i);
+}
+
+fn dispatch(c : &mut Criterion) {
+    let record = Record::new(Level::Info, format_args!("message"));
+    for sinks in [0, 1] {
+        install(sinks);
+        c.bench_function(&format!("registry {} sink(s)", sinks), |b| {
+            b.iter(|| impls::sink_impl(criterion::black_box(&record)))
+        });
+        c.bench_function(&format!("mutex {} sink(s)", sinks), |b| {
+            b.iter(|| baseline_impl(criterion::black_box(&record)))
+        });
+        for threads in [4, 8] {
+            c.bench_function(&format!("registry {} sink(s), {} threads", sinks, threads), |b| {
+                b.iter_custom(|iters| run_threads(threads, iters, registry))
+            });
+            c.bench_function(&format!("mutex {} sink(s), {} threads", sinks, threads), |b| {
+                b.iter_custom(|iters| run_threads(threads, iters, mutex))
+            });
+        }
+    }
+
+    for sinks in [1, 4] {
+        install(sinks);
+        c.bench_function(&format!("log_info! {} sink(s)", sinks), |b| {
+            b.iter(|| log_info!("message {}", 1))
+        });
+    }
+    install(1);
+    for threads in [4, 8] {
+        c.bench_function(&format!("log_info! 1 sink, {} threads", threads), |b| {
+            b.iter_custom(|iters| run_threads(threads, iters, |i| log_info!("message {}", i)))
+        });
+    }
+    install(0);
+}
+
+criterion_group!(benches, dispatch);
+criterion_main!(benches);
@@ -0,0 +1,31 @@
+//!
+//! Receives log records sent by `NetworkSink` instances and writes
+//! them as JSON lines to stdout or to a file.
+//!
+//! Usage: `log_receiver <address> [tcp|udp] [lines|length] [output file]`
+//!
+//! Example: `cargo run --example log_receiver -- 0.0.0.0:9000 tcp lines logs.jsonl`
+//!
+
+use std::fs::OpenOptions;
+use workflow_log::sinks::{Framing, LogReceiver, Transport};
+
+fn main() -> std::io::Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let addr = args.first().map(String::as_str).unwrap_or("127.0.0.1:9000");
+    let transport = match args.get(1).map(String::as_str) {
+        Some("udp") => Transport::Udp,
+        _ => Transport::Tcp,
+    };
+    let framing = match args.get(2).map(String::as_str) {
+        Some("length") => Framing::LengthPrefixed,
+        _ => Framing::JsonLines,
+    };
+
+    let receiver = LogReceiver::bind(addr, transport, framing)?;
+    eprintln!("receiving logs on {} ({:?}, {:?})", receiver.local_addr()?, transport, framing);
+    match args.get(3) {
+        Some(path) => receiver.run(OpenOptions::new().create(true).append(true).open(path)?),
+        None => receiver.run(std::io::stdout()),
+    }
+}
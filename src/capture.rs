@@ -0,0 +1,153 @@
+//!
+//! Capture of log messages for use in unit tests.
+//!
+//! [`capture_logs`] records all messages logged by the current thread
+//! while the supplied closure runs. As captures are scoped to a thread,
+//! tests running in parallel do not observe each other's messages.
+//! Messages are captured regardless of the global level filter and
+//! are still delivered to registered sinks and the console.
+//!
+
+use crate::{Level, LogEntry, Record};
+use std::cell::RefCell;
+use std::fmt;
+
+thread_local! {
+    static CAPTURES : RefCell<Vec<Vec<LogEntry>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Log messages recorded by [`capture_logs`].
+#[derive(Debug, Clone, Default)]
+pub struct CapturedLogs {
+    entries : Vec<LogEntry>,
+}
+
+impl CapturedLogs {
+    /// Returns captured messages in the order they were logged.
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns `true` if a message of the given level containing `text` was captured.
+    pub fn contains(&self, level : Level, text : &str) -> bool {
+        self.entries.iter().any(|entry| entry.level == level && entry.text.contains(text))
+    }
+}
+
+impl fmt::Display for CapturedLogs {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(f, "[{}] {}", entry.level, entry.text)?;
+        }
+        Ok(())
+    }
+}
+
+struct CaptureGuard;
+
+impl Drop for CaptureGuard {
+    fn drop(&mut self) {
+        CAPTURES.with(|captures| captures.borrow_mut().pop());
+        crate::exit_record_scope();
+    }
+}
+
+/// Runs `f` and returns its result along with all messages logged
+/// by the current thread while it was running, regardless of the
+/// global level filter. Messages logged by other threads (including
+/// threads spawned by `f`) are not captured, so tests running in
+/// parallel do not observe each other's messages.
+/// Captures can be nested, in which case messages are recorded by
+/// all enclosing captures.
+///
+/// ```
+/// use workflow_log::*;
+///
+/// let (_, logs) = capture_logs(|| {
+///     log_warning!("connection timeout");
+///     assert_logged!(Level::Warn, contains "timeout");
+/// });
+/// assert_logged!(logs, Level::Warn, contains "timeout");
+/// assert_not_logged!(logs, Level::Error, contains "timeout");
+/// ```
+pub fn capture_logs<F, R>(f : F) -> (R, CapturedLogs)
+where
+    F : FnOnce() -> R,
+{
+    CAPTURES.with(|captures| captures.borrow_mut().push(Vec::new()));
+    crate::enter_record_scope();
+    let guard = CaptureGuard;
+    let result = f();
+    let entries = CAPTURES.with(|captures| captures.borrow_mut().last_mut().map(std::mem::take));
+    drop(guard);
+    (result, CapturedLogs { entries : entries.unwrap_or_default() })
+}
+
+/// Returns messages captured so far by the innermost [`capture_logs`]
+/// running on the current thread, or `None` if no capture is active.
+pub fn captured_logs() -> Option<CapturedLogs> {
+    CAPTURES.with(|captures| {
+        captures.borrow().last().map(|entries| CapturedLogs { entries : entries.clone() })
+    })
+}
+
+/// Returns `true` if a capture is active on the current thread.
+#[inline(always)]
+pub(crate) fn is_active() -> bool {
+    CAPTURES.with(|captures| captures.try_borrow().is_ok_and(|captures| !captures.is_empty()))
+}
+
+/// Records the message into all captures active on the current thread.
+#[inline(always)]
+pub(crate) fn capture(record : &Record<'_>) {
+    CAPTURES.with(|captures| {
+        if let Ok(mut captures) = captures.try_borrow_mut() {
+            if captures.is_empty() {
+                return;
+            }
+            let entry = LogEntry::from(record);
+            for capture in captures.iter_mut() {
+                capture.push(entry.clone());
+            }
+        }
+    })
+}
+
+/// Asserts that a message of the given level containing the given text
+/// has been captured. The captured logs can be supplied explicitly as
+/// the first argument; otherwise the active [`capture_logs`] scope is checked.
+#[macro_export]
+macro_rules! assert_logged {
+    ($logs:expr, $level:expr, contains $text:expr) => {{
+        let logs = &$logs;
+        assert!(logs.contains($level, $text),
+            "expected a {} message containing {:?}, captured logs:\n{}", $level, $text, logs);
+    }};
+    ($level:expr, contains $text:expr) => {{
+        let logs = workflow_log::captured_logs().expect("assert_logged!() used outside of capture_logs()");
+        workflow_log::assert_logged!(logs, $level, contains $text);
+    }};
+}
+
+/// Asserts that no message of the given level containing the given text
+/// has been captured. Accepts the same arguments as [`assert_logged!`].
+#[macro_export]
+macro_rules! assert_not_logged {
+    ($logs:expr, $level:expr, contains $text:expr) => {{
+        let logs = &$logs;
+        assert!(!logs.contains($level, $text),
+            "unexpected {} message containing {:?}, captured logs:\n{}", $level, $text, logs);
+    }};
+    ($level:expr, contains $text:expr) => {{
+        let logs = workflow_log::captured_logs().expect("assert_not_logged!() used outside of capture_logs()");
+        workflow_log::assert_not_logged!(logs, $level, contains $text);
+    }};
+}
@@ -0,0 +1,30 @@
+use std::fmt;
+
+/// A structured key-value field attached to a log message using
+/// the `fields: { key = value, ... }` syntax of the log macros:
+///
+/// ```
+/// use workflow_log::*;
+/// let peer = "127.0.0.1:8080";
+/// log_info!(fields: { peer = peer, attempts = 3 }, "connected");
+/// ```
+///
+/// Fields are delivered to sinks via [`Record::fields`](crate::Record::fields)
+/// and are not included in the console output.
+#[derive(Clone, Copy)]
+pub struct Field<'a> {
+    pub key : &'a str,
+    pub value : &'a dyn fmt::Display,
+}
+
+impl<'a> Field<'a> {
+    pub fn new(key : &'a str, value : &'a dyn fmt::Display) -> Field<'a> {
+        Field { key, value }
+    }
+}
+
+impl fmt::Debug for Field<'_> {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
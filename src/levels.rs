@@ -1,21 +1,22 @@
-///
-/// This enum is a replica of the Level and LevelFilter
-/// enums from the log crate : https://crates.io/crates/log
-/// 
-/// The original references can be found here:
-/// - https://docs.rs/log/latest/log/enum.Level.html
-/// - https://docs.rs/log/latest/log/enum.LevelFilter.html
-/// 
-/// This is embedded directly into this crate to to allow 
-/// usage of log levels while retaining the ability to embed
-/// the log crate itself as an optional feature. This is done
-/// to accomodate reduced bytecode requirements of build
-/// targets such as BPF.
-///
+//!
+//! This enum is a replica of the Level and LevelFilter
+//! enums from the log crate : https://crates.io/crates/log
+//! 
+//! The original references can be found here:
+//! - https://docs.rs/log/latest/log/enum.Level.html
+//! - https://docs.rs/log/latest/log/enum.LevelFilter.html
+//! 
+//! This is embedded directly into this crate to to allow 
+//! usage of log levels while retaining the ability to embed
+//! the log crate itself as an optional feature. This is done
+//! to accomodate reduced bytecode requirements of build
+//! targets such as BPF.
+//!
 
 use std::cmp;
 
 #[repr(usize)]
+#[allow(clippy::derived_hash_with_manual_eq)]
 #[derive(Copy, Eq, Debug, Hash)]
 pub enum Level {
     Error = 1,
@@ -108,6 +109,7 @@ impl Ord for Level {
 }
 
 #[repr(usize)]
+#[allow(clippy::derived_hash_with_manual_eq)]
 #[derive(Copy, Eq, Debug, Hash)]
 pub enum LevelFilter {
     /// A level lower than all log levels.
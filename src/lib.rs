@@ -4,12 +4,17 @@
 //! environments including native Rust, WASM-browser and Solana OS targets.
 //! 
 //! When you application is built in the native application environment
-//! macros such as `log_info!()` will invoke `println!()`, in WASM they will
-//! invoke `console.log()` and under Solana they will invoke `sol_log()` 
+//! macros such as `log_info!()` will write to `stdout` (errors and warnings
+//! are written to `stderr`, see [`workflow_log::set_output_stream`]), in WASM
+//! they will invoke `console.log()` and under Solana they will invoke `sol_log()`
 //! (used by `msg!()` macro)
 //! 
 //! `workflow-log` macros operate the same way as regular functions such as 
-//! `println!()`
+//! `println!()`. Each message is tagged with a target, which defaults to the
+//! module path of the log statement and can be supplied explicitly using
+//! `log_info!(target: "net", "...")`. Structured key-value fields can be attached
+//! to a message using `log_info!(fields: { peer = addr }, "...")` (see
+//! [`workflow_log::Field`]); they are delivered to sinks but not output to the console.
 //! 
 //! The following core macros are available:
 //! - `log_trace!()`
@@ -18,35 +23,84 @@
 //! - `log_warn()`
 //! - `log_error!()`
 //! 
+//! Log messages can be captured in unit tests using [`workflow_log::capture_logs`]
+//! and verified using `assert_logged!()` and `assert_not_logged!()` macros.
+//! 
+//! Panics can be routed through the log subsystem (and the registered sinks)
+//! by installing a panic hook using [`workflow_log::install_panic_hook`].
+//! 
 //! # Redirecting log output
 //! 
-//! This crate allows you to configure a log sink that will receive
+//! This crate allows you to configure log sinks that will receive
 //! all log messages from your application.  This is useful to route log messages
 //! to an external receiver or, for example, store logs to a file.
 //! 
-//! Log sink can be installed using [`workflow_log::pipe`] function and supplying
-//! it with an Arc of the [`workflow_log::Sink`] trait.  The trait function
-//! [`workflow_log::Sink::write`] should return `true` to indicate the the text
-//! should be outputed to the console, or `false` to prevent further output
-//! (i.e. to consume the log text)
+//! Log sinks can be registered using [`workflow_log::add_sink`] function by supplying
+//! it with an Arc of the [`workflow_log::Sink`] trait.  Multiple sinks can be
+//! registered at the same time and each log message is delivered to all of them.
+//! [`workflow_log::add_sink`] returns a [`workflow_log::SinkId`] handle that can
+//! later be passed to [`workflow_log::remove_sink`] to unregister the sink.
+//! 
+//! Sinks receive each message as a [`workflow_log::Record`] containing the
+//! message level, target, timestamp and the location of the log statement
+//! via [`workflow_log::Sink::write_record`]. Existing sinks implementing the
+//! deprecated [`workflow_log::Sink::write`], which receives only the level and
+//! the message, keep working.
+//! 
+//! [`workflow_log::Sink::write_record`] returns a [`workflow_log::SinkOutput`]:
+//! `SinkOutput::PassThrough` to indicate that the text should be outputed to the
+//! console, or `SinkOutput::Consume` to prevent further output (i.e. to consume the
+//! log text).  It can also replace the text outputed to the console (e.g. to redact
+//! it) or output it using a different level.  [`workflow_log::Sink::write`] returns
+//! `true` to consume the log text.
+//! When multiple sinks are registered, the text is outputed to the console only if
+//! none of the sinks has consumed it, as described in [`workflow_log::SinkOutput::merge`].
 //! 
 //! ## Example:
 //! 
 //! ```
+//! use std::sync::Arc;
 //! use workflow_log::*;
 //! pub struct MyStruct;
 //! impl Sink for MyStruct {
-//!     fn write(&self, _level:Level, args : &std::fmt::Arguments<'_>) -> bool {
-//!         // return true to continue output
-//!         // return false to prevent further output
+//!     fn write_record(&self, _record : &Record<'_>) -> SinkOutput {
+//!         // return SinkOutput::PassThrough to continue output
+//!         // return SinkOutput::Consume to prevent further output
+//!         SinkOutput::PassThrough
 //!     }
 //! }
-//! ...
+//! 
+//! # #[cfg(feature = "sink")] {
 //! let my_struct = Arc::new(MyStruct{});
-//! workflow_log::pipe(Some(my_struct));
+//! let id = workflow_log::add_sink(my_struct);
+//! // ...
+//! workflow_log::remove_sink(id);
+//! # }
 //! ```
 //! 
-//! To can disable the sink by supplying [`Option::None`] to [`workflow_log::pipe`].  
+//! Sinks only receive messages allowed by the global filter set using
+//! [`workflow_log::set_log_level`]. Each sink can further restrict the messages
+//! it receives by implementing [`workflow_log::Sink::enabled`] (or
+//! [`workflow_log::Sink::enabled_for`] to filter by target), or by assigning
+//! a per-sink [`workflow_log::LevelFilter`] using [`workflow_log::set_sink_level`]
+//! and per-target filters using [`workflow_log::set_sink_target_level`].
+//! 
+//! Built-in sink implementations are available in the [`workflow_log::sinks`] module.
+//! For example, [`workflow_log::sinks::AsyncSink`] wraps a sink and delivers messages
+//! to it from a background thread through a bounded queue.
+//! 
+//! Sinks that buffer messages should implement [`workflow_log::Sink::flush`]; all
+//! registered sinks can be flushed using [`workflow_log::flush`], which applications
+//! should invoke before exiting. Sinks are notified when they are registered or
+//! unregistered via [`workflow_log::Sink::on_install`] and [`workflow_log::Sink::on_remove`].
+//! 
+//! A sink can also be installed for the current thread only, for the duration
+//! of a closure, using [`workflow_log::with_sink`]. While the closure runs, messages
+//! logged by the thread are delivered to that sink instead of the registered sinks.
+//! 
+//! The [`workflow_log::pipe`] function can be used to install a single sink,
+//! replacing all sinks registered previously. All sinks can be removed
+//! by supplying [`Option::None`] to [`workflow_log::pipe`].  
 //! 
 
 extern crate self as workflow_log;
@@ -57,4 +111,38 @@ pub use self::log::*;
 mod console;
 pub use self::console::*;
 
+mod field;
+pub use self::field::*;
+
 pub mod levels;
+
+#[cfg(not(target_os = "solana"))]
+mod record;
+#[cfg(not(target_os = "solana"))]
+pub use self::record::*;
+
+#[cfg(all(not(target_os = "solana"), not(target_arch = "wasm32")))]
+mod stream;
+#[cfg(all(not(target_os = "solana"), not(target_arch = "wasm32")))]
+pub use self::stream::*;
+
+#[cfg(all(not(target_os = "solana"), feature = "sink"))]
+mod sync;
+
+#[cfg(not(target_os = "solana"))]
+mod panic_hook;
+#[cfg(not(target_os = "solana"))]
+pub use self::panic_hook::*;
+
+#[cfg(not(target_os = "solana"))]
+mod sampling;
+#[cfg(not(target_os = "solana"))]
+pub use self::sampling::*;
+
+#[cfg(all(not(target_os = "solana"), feature = "sink"))]
+pub mod sinks;
+
+#[cfg(all(not(target_os = "solana"), feature = "sink"))]
+mod capture;
+#[cfg(all(not(target_os = "solana"), feature = "sink"))]
+pub use self::capture::*;
@@ -1,11 +1,15 @@
 use cfg_if::cfg_if;
 use std::fmt;
+use workflow_log::Field;
 
 cfg_if! {
     if #[cfg(target_os = "solana")] {
         pub use workflow_log::levels::{ Level, LevelFilter };
     } else {
+        #[cfg(feature = "sink")]
         use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use workflow_log::Record;
         pub use log::{ Level, LevelFilter };
         use downcast::{ downcast_sync, AnySync };
         pub use hexplay::{self, HexViewBuilder};
@@ -66,15 +70,357 @@ cfg_if! {
             }
         }
 
-        /// A log sink trait that can be installed into the log subsystem using the [`pipe`] 
-        /// function and will receive all log messages.
+        /// Determines how a log message is output to the console after it
+        /// has been delivered to a sink. Returned by [`Sink::write_record`].
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub enum SinkOutput {
+            /// Output the message to the console as usual.
+            PassThrough,
+            /// The message has been consumed and should not be output to the console.
+            Consume,
+            /// Output the supplied text to the console in place of the message,
+            /// e.g. to redact or annotate it.
+            Replace(String),
+            /// Output the message to the console using the supplied level.
+            Escalate(Level),
+        }
+
+        impl SinkOutput {
+            /// Combines the results of delivering a message to multiple sinks.
+            /// A message consumed by any of the sinks is consumed; otherwise
+            /// the first result other than [`SinkOutput::PassThrough`] applies.
+            pub fn merge(self, other : SinkOutput) -> SinkOutput {
+                match (self, other) {
+                    (SinkOutput::Consume, _) | (_, SinkOutput::Consume) => SinkOutput::Consume,
+                    (SinkOutput::PassThrough, other) => other,
+                    (output, _) => output,
+                }
+            }
+
+            /// Returns `true` if the message has been consumed.
+            pub fn is_consumed(&self) -> bool {
+                matches!(self, SinkOutput::Consume)
+            }
+        }
+
+        impl From<bool> for SinkOutput {
+            /// Converts the result of [`Sink::write`], where `true`
+            /// indicates that the message has been consumed.
+            fn from(consumed : bool) -> SinkOutput {
+                if consumed { SinkOutput::Consume } else { SinkOutput::PassThrough }
+            }
+        }
+
+        /// A log sink trait that can be installed into the log subsystem using the [`add_sink`]
+        /// or [`pipe`] functions and will receive all log messages.
+        ///
+        /// Sinks receive messages via [`Sink::write_record`] or [`Sink::try_write_record`],
+        /// each of which by default forwards the message to the other. Implementations
+        /// must override one of these functions (or the deprecated [`Sink::write`],
+        /// which receives the message if neither is overridden).
         pub trait Sink : AnySync {
-            fn write(&self, level : Level, args : &fmt::Arguments<'_>) -> bool;
+            /// Receives a log message. Returning `true` indicates that the message
+            /// has been consumed and should not be output to the console.
+            ///
+            /// This function is only retained so that existing sinks implementing
+            /// it keep working: it is invoked by the default [`Sink::write_record`]
+            /// if [`Sink::try_write_record`] is not overridden either, and should
+            /// not be called directly, as sinks implementing [`Sink::write_record`]
+            /// (including all built-in sinks) do not implement it. The default
+            /// implementation ignores the message.
+            #[deprecated(note = "implement and call `Sink::write_record` instead")]
+            fn write(&self, _level : Level, _args : &fmt::Arguments<'_>) -> bool {
+                false
+            }
+
+            /// Receives a log message along with its metadata. The returned
+            /// [`SinkOutput`] determines how the message is output to the console.
+            /// By default the message is forwarded to [`Sink::try_write_record`],
+            /// ignoring its errors.
+            fn write_record(&self, record : &Record<'_>) -> SinkOutput {
+                let sink = (self as *const Self as *const (), std::any::TypeId::of::<Self>());
+                match forward_to_try_write_record(sink, || self.try_write_record(record)) {
+                    Some(result) => result.unwrap_or(SinkOutput::PassThrough),
+                    // neither `write_record` nor `try_write_record` is implemented
+                    #[allow(deprecated)]
+                    None => self.write(record.level, &record.args).into()
+                }
+            }
+
+            /// Receives a log message along with its metadata, reporting an error
+            /// if the sink has failed to process it. Used by sinks such as
+            /// [`sinks::Fallback`](crate::sinks::Fallback) that need to detect failures.
+            /// Sinks that are able to fail should implement this function in place
+            /// of [`Sink::write_record`]. By default the message is forwarded to
+            /// [`Sink::write_record`].
+            fn try_write_record(&self, record : &Record<'_>) -> std::io::Result<SinkOutput> {
+                Ok(self.write_record(record))
+            }
+
+            /// Returns `true` if the sink is interested in messages of the
+            /// given level. Messages for which this function returns `false`
+            /// are not delivered to the sink.
+            fn enabled(&self, _level : Level) -> bool {
+                true
+            }
+
+            /// Returns `true` if the sink is interested in messages of the given
+            /// level and target. Messages for which this function returns `false`
+            /// are not delivered to the sink. By default only the level is checked
+            /// using [`Sink::enabled`]; sinks filtering messages by target should
+            /// override this function.
+            fn enabled_for(&self, level : Level, _target : &str) -> bool {
+                self.enabled(level)
+            }
+
+            /// Flushes any buffered messages. Invoked by [`flush`].
+            fn flush(&self) {}
+
+            /// Invoked after the sink has been registered.
+            fn on_install(&self) {}
+
+            /// Invoked after the sink has been unregistered using
+            /// [`remove_sink`] or replaced using [`pipe`].
+            fn on_remove(&self) {}
         }
-        
+
+        thread_local! {
+            /// Sink whose default [`Sink::write_record`] is forwarding a message
+            /// to its [`Sink::try_write_record`] on the current thread, identified
+            /// by its address and type (a sink may share its address with a
+            /// sink it contains).
+            static FORWARDING_SINK : std::cell::Cell<Option<SinkKey>> = const { std::cell::Cell::new(None) };
+        }
+
+        type SinkKey = (*const (), std::any::TypeId);
+
+        struct ForwardingGuard(Option<SinkKey>);
+
+        impl Drop for ForwardingGuard {
+            fn drop(&mut self) {
+                FORWARDING_SINK.with(|sink| sink.set(self.0));
+            }
+        }
+
+        /// Runs `f` invoking [`Sink::try_write_record`] of `sink` on behalf of its
+        /// default [`Sink::write_record`]. Returns `None` without running `f` if the
+        /// sink is already doing so, i.e. if its [`Sink::try_write_record`] is not
+        /// overridden and has forwarded the message back to [`Sink::write_record`].
+        fn forward_to_try_write_record<R>(sink : SinkKey, f : impl FnOnce() -> R) -> Option<R> {
+            let previous = FORWARDING_SINK.with(|forwarding| forwarding.replace(Some(sink)));
+            let _guard = ForwardingGuard(previous);
+            (previous != Some(sink)).then(f)
+        }
+
+        /// Handle identifying a sink registered with [`add_sink`]. It can be
+        /// passed to [`remove_sink`] to uninstall the sink.
+        #[cfg(feature = "sink")]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub struct SinkId(usize);
+
+        #[cfg(feature = "sink")]
+        impl SinkId {
+            fn next() -> SinkId {
+                static NEXT_SINK_ID : AtomicUsize = AtomicUsize::new(1);
+                SinkId(NEXT_SINK_ID.fetch_add(1, Ordering::Relaxed))
+            }
+        }
+
+        #[cfg(feature = "sink")]
+        #[derive(Clone)]
         struct SinkHandler {
-            // #[allow(dead_code)]
-            sink : Arc<dyn Sink>, // + Send + Sync + 'static>,
+            id : SinkId,
+            sink : Arc<dyn Sink>,
+            level_filter : LevelFilter,
+            /// Filters overriding `level_filter` for targets starting with the given prefix
+            target_filters : Vec<(String, LevelFilter)>,
+        }
+
+        #[cfg(feature = "sink")]
+        impl SinkHandler {
+            fn new(sink : Arc<dyn Sink>) -> SinkHandler {
+                SinkHandler { id : SinkId::next(), sink, level_filter : LevelFilter::Trace, target_filters : Vec::new() }
+            }
+
+            /// Returns the filter of the longest target prefix matching
+            /// the target, or the filter of the sink if none matches.
+            fn level_filter(&self, target : &str) -> LevelFilter {
+                self.target_filters.iter()
+                    .filter(|(prefix, _)| target.starts_with(prefix.as_str()))
+                    .max_by_key(|(prefix, _)| prefix.len())
+                    .map_or(self.level_filter, |(_, level_filter)| *level_filter)
+            }
+
+            fn set_target_level(&mut self, target : &str, level : LevelFilter) {
+                self.target_filters.retain(|(prefix, _)| prefix != target);
+                self.target_filters.push((target.to_string(), level));
+            }
+
+            #[inline(always)]
+            fn enabled(&self, level : Level, target : &str) -> bool {
+                level <= self.level_filter(target) && self.sink.enabled_for(level, target)
+            }
+        }
+
+        /// Delivers a message to every registered sink (in registration order)
+        /// that has the message level and target enabled and returns their results
+        /// combined using [`SinkOutput::merge`]. All sinks receive the message
+        /// regardless of the results returned by the sinks preceding them.
+        #[cfg(feature = "sink")]
+        fn dispatch(handlers : &[SinkHandler], record : &Record<'_>) -> SinkOutput {
+            let mut output = SinkOutput::PassThrough;
+            for handler in handlers.iter() {
+                if handler.enabled(record.level, record.target) {
+                    output = output.merge(handler.sink.write_record(record));
+                }
+            }
+            output
+        }
+
+        /// Consumers of records across all threads: whether any sinks are
+        /// registered and the number of active [`capture_logs`](crate::capture_logs)
+        /// captures and [`with_sink`] scopes. Log calls load it once and only
+        /// check the thread-local captures and sinks, or the registered sinks,
+        /// if any thread may have them. While zero, log calls output messages
+        /// directly to the console.
+        #[cfg(feature = "sink")]
+        static RECORD_CONSUMERS : AtomicUsize = AtomicUsize::new(0);
+
+        /// Value of [`RECORD_CONSUMERS`] loaded by a log call.
+        #[cfg(feature = "sink")]
+        #[derive(Clone, Copy)]
+        struct Consumers(usize);
+
+        #[cfg(feature = "sink")]
+        impl Consumers {
+            /// Bit set while the list of registered sinks is non-empty.
+            const SINKS : usize = 1;
+            /// Amount added for each active capture and [`with_sink`] scope.
+            const SCOPE : usize = 2;
+
+            /// Returns `true` if records may be consumed by any thread. If `false`,
+            /// no sinks are registered and no thread is capturing messages or
+            /// has a sink installed using [`with_sink`].
+            #[inline(always)]
+            fn any(self) -> bool {
+                self.0 != 0
+            }
+
+            /// Returns `true` if any sinks are registered.
+            #[inline(always)]
+            fn sinks(self) -> bool {
+                self.0 & Consumers::SINKS != 0
+            }
+
+            /// Returns `true` if any thread is capturing messages or has
+            /// a sink installed using [`with_sink`].
+            #[inline(always)]
+            fn scopes(self) -> bool {
+                self.0 >= Consumers::SCOPE
+            }
+        }
+
+        /// Registers a capture or [`with_sink`] scope (see [`RECORD_CONSUMERS`]).
+        #[cfg(feature = "sink")]
+        pub(crate) fn enter_record_scope() {
+            RECORD_CONSUMERS.fetch_add(Consumers::SCOPE, Ordering::Relaxed);
+        }
+
+        /// Unregisters a scope registered using [`enter_record_scope`].
+        #[cfg(feature = "sink")]
+        pub(crate) fn exit_record_scope() {
+            RECORD_CONSUMERS.fetch_sub(Consumers::SCOPE, Ordering::Relaxed);
+        }
+
+        /// Records whether any sinks are registered (see [`RECORD_CONSUMERS`]).
+        #[cfg(feature = "sink")]
+        fn set_sinks_registered(registered : bool) {
+            if registered {
+                RECORD_CONSUMERS.fetch_or(Consumers::SINKS, Ordering::Relaxed);
+            } else {
+                RECORD_CONSUMERS.fetch_and(!Consumers::SINKS, Ordering::Relaxed);
+            }
+        }
+
+        #[cfg(feature = "sink")]
+        #[inline(always)]
+        fn record_consumers() -> Consumers {
+            Consumers(RECORD_CONSUMERS.load(Ordering::Relaxed))
+        }
+
+        #[cfg(feature = "sink")]
+        thread_local! {
+            static SCOPED_SINKS : std::cell::RefCell<Vec<Arc<dyn Sink>>> = const { std::cell::RefCell::new(Vec::new()) };
+            static IN_SINK : std::cell::Cell<bool> = const { std::cell::Cell::new(false) };
+        }
+
+        #[cfg(feature = "sink")]
+        struct InSinkGuard(bool);
+
+        #[cfg(feature = "sink")]
+        impl Drop for InSinkGuard {
+            fn drop(&mut self) {
+                IN_SINK.with(|in_sink| in_sink.set(self.0));
+            }
+        }
+
+        /// Runs `f` marking the current thread as executing sink code.
+        /// Messages logged by `f` (e.g. a sink reporting its own failure)
+        /// bypass the sinks and the log capture and are output directly
+        /// to the console, so a sink can not recurse into itself or
+        /// deadlock on its own locks.
+        #[cfg(feature = "sink")]
+        pub(crate) fn in_sink_scope<R>(f : impl FnOnce() -> R) -> R {
+            let _guard = InSinkGuard(IN_SINK.with(|in_sink| in_sink.replace(true)));
+            f()
+        }
+
+        /// Returns `true` if the current thread is executing sink code.
+        #[cfg(feature = "sink")]
+        #[inline(always)]
+        pub(crate) fn in_sink() -> bool {
+            IN_SINK.with(|in_sink| in_sink.get())
+        }
+
+        #[cfg(feature = "sink")]
+        struct ScopedSinkGuard;
+
+        #[cfg(feature = "sink")]
+        impl Drop for ScopedSinkGuard {
+            fn drop(&mut self) {
+                SCOPED_SINKS.with(|sinks| sinks.borrow_mut().pop());
+                exit_record_scope();
+            }
+        }
+
+        /// Runs `f` with `sink` receiving all messages logged by the current
+        /// thread in place of the globally registered sinks. Messages logged
+        /// by other threads (including threads spawned by `f`) are delivered
+        /// to the global sinks as usual. Scopes can be nested, in which case
+        /// the innermost sink receives the messages.
+        #[cfg(feature = "sink")]
+        pub fn with_sink<F, R>(sink : Arc<dyn Sink>, f : F) -> R
+        where
+            F : FnOnce() -> R,
+        {
+            SCOPED_SINKS.with(|sinks| sinks.borrow_mut().push(sink));
+            enter_record_scope();
+            let _guard = ScopedSinkGuard;
+            f()
+        }
+
+        /// Returns `true` if a sink has been installed for the current thread using [`with_sink`].
+        #[cfg(feature = "sink")]
+        #[inline(always)]
+        fn scoped_sink_installed() -> bool {
+            SCOPED_SINKS.with(|sinks| sinks.try_borrow().is_ok_and(|sinks| !sinks.is_empty()))
+        }
+
+        /// Returns the sink installed for the current thread using [`with_sink`].
+        #[cfg(feature = "sink")]
+        #[inline(always)]
+        fn scoped_sink() -> Option<Arc<dyn Sink>> {
+            SCOPED_SINKS.with(|sinks| sinks.try_borrow().ok().and_then(|sinks| sinks.last().cloned()))
         }
 
         downcast_sync!(dyn Sink);
@@ -87,85 +433,154 @@ cfg_if! {
         pub fn log_level_enabled(_level: Level) -> bool { 
             true
         }
-    } else if #[cfg(target_arch = "wasm32")] {
-        static mut LEVEL_FILTER : LevelFilter = LevelFilter::Trace;
-        #[inline(always)]
-        pub fn log_level_enabled(level: Level) -> bool { 
-            unsafe { LEVEL_FILTER >= level } 
-        }
-        pub fn set_log_level(level: LevelFilter) { 
-            unsafe { LEVEL_FILTER = level };
-        }
-        cfg_if! {
-            if #[cfg(feature = "sink")] {
-                static mut SINK : Option<SinkHandler> = None;
-                // pub fn pipe(sink : Arc<dyn Sink + Send + Sync + 'static>) {
-                pub fn pipe(sink : Option<Arc<dyn Sink>>) {
-                    match sink {
-                        Some(sink) => { unsafe { SINK = Some(SinkHandler { sink }); } },
-                        None => { unsafe { SINK = None; } }
-                    }
-                }
-                #[inline(always)]
-                fn to_sink(level : Level, args : &fmt::Arguments<'_>) -> bool {
-                    let sink = unsafe { &SINK };
-                    match sink {
-                        Some(handler) => {
-                            handler.sink.write(level, args)
-                        },
-                        None => { false }
-                    }
-                }
-            }
-        }
-        
     } else {
+        #[cfg(feature = "sink")]
         use std::sync::Mutex;
 
-        lazy_static::lazy_static! {
-            static ref LEVEL_FILTER : Mutex<LevelFilter> = Mutex::new(LevelFilter::Trace);
-        }
+        /// Current [`LevelFilter`] stored as its `usize` discriminant,
+        /// so that level checks are a single relaxed atomic load.
+        static LEVEL_FILTER : AtomicUsize = AtomicUsize::new(LevelFilter::Trace as usize);
+
         #[inline(always)]
         /// Returns true if the current log level is below the
         /// currently set [`LevelFilter`]
         pub fn log_level_enabled(level: Level) -> bool {
-            *LEVEL_FILTER.lock().unwrap() >= level
+            level as usize <= LEVEL_FILTER.load(Ordering::Relaxed)
         }
         /// Enable filtering of log messages using the [`LevelFilter`]
         pub fn set_log_level(level: LevelFilter) {
-            *LEVEL_FILTER.lock().unwrap() = level;
+            LEVEL_FILTER.store(level as usize, Ordering::Relaxed);
         }
         cfg_if! {
             if #[cfg(feature = "sink")] {
-                lazy_static::lazy_static! {
-                    static ref SINK : Mutex<Option<SinkHandler>> = Mutex::new(None);
+                use arc_swap::ArcSwapOption;
+
+                /// Registered sinks. Logging threads only perform an atomic load
+                /// of the current list, while updates replace the list with a
+                /// modified copy (read-copy-update). `None` if no sinks are registered,
+                /// which is mirrored by [`RECORD_CONSUMERS`].
+                static SINKS : ArcSwapOption<Vec<SinkHandler>> = ArcSwapOption::const_empty();
+                /// Serializes updates of [`SINKS`].
+                static SINKS_UPDATE : Mutex<()> = Mutex::new(());
+
+                /// Replaces the list of registered sinks with the result of `f`
+                /// applied to a copy of the current list. Returns the result of `f`.
+                fn update_sinks<R>(f : impl FnOnce(&mut Vec<SinkHandler>) -> R) -> R {
+                    let _lock = crate::sync::lock(&SINKS_UPDATE);
+                    let mut sinks = SINKS.load().as_deref().cloned().unwrap_or_default();
+                    let result = f(&mut sinks);
+                    let registered = !sinks.is_empty();
+                    SINKS.store(registered.then(|| Arc::new(sinks)));
+                    set_sinks_registered(registered);
+                    result
+                }
+
+                /// Receives an `Arc`ed [`Sink`] trait reference and registers it
+                /// as a log sink / receiver alongside any other registered sinks.
+                /// Returns a [`SinkId`] that can be used to unregister the sink
+                /// using [`remove_sink`].
+                pub fn add_sink(sink : Arc<dyn Sink>) -> SinkId {
+                    let handler = SinkHandler::new(sink.clone());
+                    let id = handler.id;
+                    update_sinks(|sinks| sinks.push(handler));
+                    in_sink_scope(|| sink.on_install());
+                    id
+                }
+                /// Unregisters a sink previously registered with [`add_sink`],
+                /// returning it if it was still installed.
+                pub fn remove_sink(id : SinkId) -> Option<Arc<dyn Sink>> {
+                    let sink = update_sinks(|sinks| {
+                        let index = sinks.iter().position(|handler| handler.id == id)?;
+                        Some(sinks.remove(index).sink)
+                    })?;
+                    in_sink_scope(|| sink.on_remove());
+                    Some(sink)
+                }
+                /// Restricts messages delivered to the sink identified by `id`
+                /// to the supplied [`LevelFilter`]. This filter is applied in addition
+                /// to the global filter set by [`set_log_level`] and the sink's own
+                /// [`Sink::enabled_for`] check. Returns `false` if the sink is not registered.
+                pub fn set_sink_level(id : SinkId, level : LevelFilter) -> bool {
+                    update_sinks(|sinks| {
+                        match sinks.iter_mut().find(|handler| handler.id == id) {
+                            Some(handler) => { handler.level_filter = level; true },
+                            None => false
+                        }
+                    })
+                }
+                /// Restricts messages whose target starts with `target` (e.g. `"net"`
+                /// or `"my_app::db"`) delivered to the sink identified by `id` to the
+                /// supplied [`LevelFilter`], in place of the filter set using
+                /// [`set_sink_level`]. If several prefixes match a target, the longest
+                /// one applies. Returns `false` if the sink is not registered.
+                pub fn set_sink_target_level(id : SinkId, target : &str, level : LevelFilter) -> bool {
+                    update_sinks(|sinks| {
+                        match sinks.iter_mut().find(|handler| handler.id == id) {
+                            Some(handler) => { handler.set_target_level(target, level); true },
+                            None => false
+                        }
+                    })
                 }
-                // pub fn pipe(sink : Option<Arc<dyn Sink + Send + Sync + 'static>>) {
                 /// Receives an Option with an `Arc`ed [`Sink`] trait reference
-                /// and installs it as a log sink / receiver.
-                /// The sink can be later disabled by invoking `pipe(None)`
+                /// and installs it as the only log sink / receiver, replacing
+                /// all sinks registered previously.
+                /// All sinks can be later removed by invoking `pipe(None)`
                 pub fn pipe(sink : Option<Arc<dyn Sink>>) {
-                    match sink {
-                        Some(sink) => { *SINK.lock().unwrap() = Some(SinkHandler { sink }); },
-                        None => { *SINK.lock().unwrap() = None; }
+                    let removed = update_sinks(|sinks| {
+                        let removed = std::mem::take(sinks);
+                        if let Some(sink) = sink.clone() {
+                            sinks.push(SinkHandler::new(sink));
+                        }
+                        removed
+                    });
+                    in_sink_scope(|| {
+                        for handler in removed {
+                            handler.sink.on_remove();
+                        }
+                        if let Some(sink) = sink {
+                            sink.on_install();
+                        }
+                    });
+                }
+                /// Flushes all registered sinks by invoking [`Sink::flush`].
+                /// Other threads can continue logging while sinks are flushed.
+                pub fn flush() {
+                    if let Some(sinks) = SINKS.load_full() {
+                        in_sink_scope(|| {
+                            for handler in sinks.iter() {
+                                handler.sink.flush();
+                            }
+                        });
                     }
-                    
                 }
+                /// Delivers the record to the sink installed for the current thread or
+                /// the registered sinks. Unless a thread has a capture or [`with_sink`]
+                /// scope, this only loads the registered sinks.
                 #[inline(always)]
-                fn to_sink(level : Level, args : &fmt::Arguments<'_>) -> bool {
-                    match SINK.lock().unwrap().as_ref() {
-                        Some(handler) => {
-                            handler.sink.write(level, args)
-                        },
-                        None => { false }
+                fn to_sink(record : &Record<'_>, consumers : Consumers) -> SinkOutput {
+                    if consumers.scopes() {
+                        if let Some(sink) = scoped_sink() {
+                            return if sink.enabled_for(record.level, record.target) {
+                                sink.write_record(record)
+                            } else {
+                                SinkOutput::PassThrough
+                            };
+                        }
+                    }
+                    if !consumers.sinks() {
+                        return SinkOutput::PassThrough;
+                    }
+                    match SINKS.load().as_deref() {
+                        Some(sinks) => dispatch(sinks, record),
+                        None => SinkOutput::PassThrough
                     }
                 }
             }
         }
 
-        #[cfg(feature = "external-logger")]
+        #[cfg(all(not(target_arch = "wasm32"), feature = "external-logger"))]
         mod workflow_logger {
-            use log::{ Level, LevelFilter, Record, Metadata, SetLoggerError };
+            use log::{ LevelFilter, Metadata, SetLoggerError };
 
             pub struct WorkflowLogger;
 
@@ -174,19 +589,23 @@ cfg_if! {
                     super::log_level_enabled(metadata.level())
                 }
     
-                fn log(&self, record: &Record) {
-                    if self.enabled(record.metadata()) {
-                        match record.metadata().level() {
-                            Level::Error => { super::error_impl(record.args()); },
-                            Level::Warn => { super::warn_impl(record.args()); },
-                            Level::Info => { super::info_impl(record.args()); },
-                            Level::Debug => { super::debug_impl(record.args()); },
-                            Level::Trace => { super::trace_impl(record.args()); },
-                        }
-                    }
+                fn log(&self, record: &log::Record) {
+                    super::impls::record_impl(&workflow_log::Record {
+                        level : record.level(),
+                        args : *record.args(),
+                        target : record.target(),
+                        module_path : record.module_path(),
+                        file : record.file(),
+                        line : record.line(),
+                        timestamp : workflow_log::system_time(),
+                        fields : &[],
+                    });
                 }
     
-                fn flush(&self) {}
+                fn flush(&self) {
+                    #[cfg(feature = "sink")]
+                    super::flush();
+                }
             }
 
             static LOGGER: WorkflowLogger = WorkflowLogger;
@@ -197,7 +616,7 @@ cfg_if! {
             }
         }
 
-        #[cfg(feature = "external-logger")]
+        #[cfg(all(not(target_arch = "wasm32"), feature = "external-logger"))]
         pub fn init() -> Result<(), log::SetLoggerError> {
             workflow_logger::init()
         }
@@ -217,6 +636,22 @@ pub mod wasm {
         pub fn warn(s: &str);
         #[wasm_bindgen(js_namespace = console)]
         pub fn error(s: &str);
+        #[wasm_bindgen(js_namespace = Date)]
+        pub fn now() -> f64;
+    }
+}
+
+/// Returns the current system time. `SystemTime::now()` is not
+/// available in the browser, so under WASM the time is obtained
+/// from `Date.now()`.
+#[cfg(not(target_os = "solana"))]
+pub(crate) fn system_time() -> std::time::SystemTime {
+    cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            std::time::UNIX_EPOCH + std::time::Duration::from_millis(wasm::now() as u64)
+        } else {
+            std::time::SystemTime::now()
+        }
     }
 }
 
@@ -224,146 +659,265 @@ pub mod wasm {
 pub mod impls {
     use super::*;
 
-    pub fn error_impl(args : &fmt::Arguments<'_>) {
-        if log_level_enabled(Level::Error) {
-            #[cfg(all(not(target_os = "solana"),feature = "sink"))] {
-                if to_sink(Level::Error, args) {
+    /// Entry point of the log macros, receiving the message
+    /// along with the location of the log statement and its fields.
+    #[inline(always)]
+    pub fn log_impl(level : Level, target : &str, module_path : &'static str, file : &'static str, line : u32, fields : &[Field<'_>], args : &fmt::Arguments<'_>) {
+        cfg_if! {
+            if #[cfg(target_os = "solana")] {
+                let _ = (target, module_path, file, line, fields);
+                level_impl(level, args);
+            } else if #[cfg(feature = "sink")] {
+                let output = output_enabled(level);
+                let consumers = record_consumers();
+                if !needs_record(consumers, output) {
+                    if output {
+                        console_impl(level, args);
+                    }
                     return;
                 }
-            }
-            cfg_if! {
-                if #[cfg(target_arch = "wasm32")] {
-                    workflow_log::wasm::error(&args.to_string());
-                } else if #[cfg(target_os = "solana")] {
-                    solana_program::log::sol_log(&args.to_string());
-                } else {
-                    println!("{}",args.to_string());
+                output_impl(&Record {
+                    level,
+                    args : *args,
+                    target,
+                    module_path : Some(module_path),
+                    file : Some(file),
+                    line : Some(line),
+                    timestamp : system_time(),
+                    fields,
+                }, output, consumers);
+            } else {
+                let _ = (target, module_path, file, line, fields);
+                if output_enabled(level) {
+                    console_impl(level, args);
                 }
             }
         }
     }
 
-    pub fn warn_impl(args : &fmt::Arguments<'_>) {
-        if log_level_enabled(Level::Warn) {
-            #[cfg(all(not(target_os = "solana"),feature = "sink"))] {
-                if to_sink(Level::Warn, args) {
-                    return;
-                }
-            }
-            cfg_if! {
-                if #[cfg(target_arch = "wasm32")] {
-                    workflow_log::wasm::warn(&args.to_string());
-                } else if #[cfg(target_os = "solana")] {
-                    solana_program::log::sol_log(&args.to_string());
-                } else {
-                    println!("{}",args.to_string());
+    /// Outputs a [`Record`] to the registered sinks and the console.
+    #[cfg(not(target_os = "solana"))]
+    pub fn record_impl(record : &Record<'_>) {
+        let output = output_enabled(record.level);
+        cfg_if! {
+            if #[cfg(feature = "sink")] {
+                output_impl(record, output, record_consumers());
+            } else {
+                if output {
+                    console_impl(record.level, &record.args);
                 }
             }
         }
     }
 
-    pub fn info_impl(args : &fmt::Arguments<'_>) {
-        if log_level_enabled(Level::Info) {
-            #[cfg(all(not(target_os = "solana"),feature = "sink"))] {
-                if to_sink(Level::Info, args) {
-                    return;
-                }
-            }
-            cfg_if! {
-                if #[cfg(target_arch = "wasm32")] {
-                    workflow_log::wasm::log(&args.to_string());
-                } else if #[cfg(target_os = "solana")] {
-                    solana_program::log::sol_log(&args.to_string());
-                } else {
-                    println!("{}",args.to_string());
+    /// Delivers a [`Record`] to the sink installed for the current thread
+    /// or the registered sinks, without capturing it or outputting it to
+    /// the console, and returns their combined [`SinkOutput`]. Returns
+    /// [`SinkOutput::PassThrough`] if no sinks are installed.
+    #[doc(hidden)]
+    #[cfg(all(not(target_os = "solana"), feature = "sink"))]
+    pub fn sink_impl(record : &Record<'_>) -> SinkOutput {
+        let consumers = record_consumers();
+        if !consumers.any() || in_sink() {
+            return SinkOutput::PassThrough;
+        }
+        in_sink_scope(|| to_sink(record, consumers))
+    }
+
+    /// Captures the record and, if `output` is `true`, delivers
+    /// it to the registered sinks and outputs it to the console
+    /// as determined by the sinks. Records logged from within
+    /// a sink are only output to the console.
+    #[cfg(all(not(target_os = "solana"), feature = "sink"))]
+    fn output_impl(record : &Record<'_>, output : bool, consumers : Consumers) {
+        let sink_output = if in_sink() {
+            SinkOutput::PassThrough
+        } else {
+            in_sink_scope(|| {
+                if consumers.scopes() {
+                    workflow_log::capture::capture(record);
                 }
+                if output { to_sink(record, consumers) } else { SinkOutput::PassThrough }
+            })
+        };
+        if output {
+            match sink_output {
+                SinkOutput::PassThrough => console_impl(record.level, &record.args),
+                SinkOutput::Consume => { },
+                SinkOutput::Replace(text) => console_impl(record.level, &format_args!("{}", text)),
+                SinkOutput::Escalate(level) => console_impl(level, &record.args),
             }
         }
     }
 
-    pub fn debug_impl(args : &fmt::Arguments<'_>) {
-        if log_level_enabled(Level::Debug) {
-            #[cfg(all(not(target_os = "solana"),feature = "sink"))] {
-                if to_sink(Level::Debug, args) {
-                    return;
+    /// Returns `true` if a message of the given level passes the
+    /// global level filter and the sampling policy of its level.
+    #[cfg(not(target_os = "solana"))]
+    #[inline(always)]
+    fn output_enabled(level : Level) -> bool {
+        log_level_enabled(level) && workflow_log::sampling::sampled(level)
+    }
+
+    /// Returns `true` if a message needs to be turned into a [`Record`],
+    /// i.e. if messages are being captured or if the message is to be
+    /// output (see [`output_enabled`]) and sinks are installed. Otherwise
+    /// the message is output directly to the console, if at all. Unless
+    /// any thread has captures or [`with_sink`] scopes, this only checks
+    /// `consumers`.
+    #[cfg(all(not(target_os = "solana"), feature = "sink"))]
+    #[inline(always)]
+    fn needs_record(consumers : Consumers, output : bool) -> bool {
+        if !consumers.scopes() {
+            return output && consumers.sinks();
+        }
+        (output && (consumers.sinks() || scoped_sink_installed())) || workflow_log::capture::is_active()
+    }
+
+    fn level_impl(level : Level, args : &fmt::Arguments<'_>) {
+        cfg_if! {
+            if #[cfg(target_os = "solana")] {
+                if log_level_enabled(level) {
+                    console_impl(level, args);
                 }
-            }
-            cfg_if! {
-                if #[cfg(target_arch = "wasm32")] {
-                    workflow_log::wasm::log(&args.to_string());
-                } else if #[cfg(target_os = "solana")] {
-                    solana_program::log::sol_log(&args.to_string());
-                } else {
-                    println!("{}",args.to_string());
+            } else if #[cfg(feature = "sink")] {
+                let output = output_enabled(level);
+                let consumers = record_consumers();
+                if needs_record(consumers, output) {
+                    output_impl(&Record::new(level, *args), output, consumers);
+                } else if output {
+                    console_impl(level, args);
+                }
+            } else {
+                if output_enabled(level) {
+                    console_impl(level, args);
                 }
             }
         }
     }
 
-    pub fn trace_impl(args : &fmt::Arguments<'_>) {
-        if log_level_enabled(Level::Trace) {
-            #[cfg(all(not(target_os = "solana"),feature = "sink"))] {
-                if to_sink(Level::Trace, args) {
-                    return;
-                }
-            }
-            cfg_if! {
-                if #[cfg(target_arch = "wasm32")] {
-                    workflow_log::wasm::log(&args.to_string());
-                } else if #[cfg(target_os = "solana")] {
-                    solana_program::log::sol_log(&args.to_string());
-                } else {
-                    println!("{}",args.to_string());
+    pub(crate) fn console_impl(level : Level, args : &fmt::Arguments<'_>) {
+        cfg_if! {
+            if #[cfg(target_arch = "wasm32")] {
+                match level {
+                    Level::Error => workflow_log::wasm::error(&args.to_string()),
+                    Level::Warn => workflow_log::wasm::warn(&args.to_string()),
+                    _ => workflow_log::wasm::log(&args.to_string()),
                 }
+            } else if #[cfg(target_os = "solana")] {
+                let _ = level;
+                solana_program::log::sol_log(&args.to_string());
+            } else {
+                workflow_log::stream::write_line(level, args);
             }
         }
     }
+
+    pub fn error_impl(args : &fmt::Arguments<'_>) {
+        level_impl(Level::Error, args);
+    }
+
+    pub fn warn_impl(args : &fmt::Arguments<'_>) {
+        level_impl(Level::Warn, args);
+    }
+
+    pub fn info_impl(args : &fmt::Arguments<'_>) {
+        level_impl(Level::Info, args);
+    }
+
+    pub fn debug_impl(args : &fmt::Arguments<'_>) {
+        level_impl(Level::Debug, args);
+    }
+
+    pub fn trace_impl(args : &fmt::Arguments<'_>) {
+        level_impl(Level::Trace, args);
+    }
 }
 
 /// Format and log message with [`Level::Error`]
 #[macro_export]
 macro_rules! log_error {
+    (target: $target:expr, fields: { $($key:ident = $value:expr),* $(,)? }, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Error, $target, module_path!(), file!(), line!(), &[$(workflow_log::Field::new(stringify!($key), &$value)),*], &format_args!($($t)*))
+    );
+    (target: $target:expr, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Error, $target, module_path!(), file!(), line!(), &[], &format_args!($($t)*))
+    );
+    (fields: { $($key:ident = $value:expr),* $(,)? }, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Error, module_path!(), module_path!(), file!(), line!(), &[$(workflow_log::Field::new(stringify!($key), &$value)),*], &format_args!($($t)*))
+    );
     ($($t:tt)*) => (
-        workflow_log::impls::error_impl(&format_args!($($t)*))
+        workflow_log::impls::log_impl(workflow_log::Level::Error, module_path!(), module_path!(), file!(), line!(), &[], &format_args!($($t)*))
     )
 }
 
 /// Format and log message with [`Level::Warn`]
 #[macro_export]
 macro_rules! log_warning {
+    (target: $target:expr, fields: { $($key:ident = $value:expr),* $(,)? }, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Warn, $target, module_path!(), file!(), line!(), &[$(workflow_log::Field::new(stringify!($key), &$value)),*], &format_args!($($t)*))
+    );
+    (target: $target:expr, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Warn, $target, module_path!(), file!(), line!(), &[], &format_args!($($t)*))
+    );
+    (fields: { $($key:ident = $value:expr),* $(,)? }, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Warn, module_path!(), module_path!(), file!(), line!(), &[$(workflow_log::Field::new(stringify!($key), &$value)),*], &format_args!($($t)*))
+    );
     ($($t:tt)*) => (
-        workflow_log::impls::warn_impl(&format_args!($($t)*))
+        workflow_log::impls::log_impl(workflow_log::Level::Warn, module_path!(), module_path!(), file!(), line!(), &[], &format_args!($($t)*))
     )
 }
 
 /// Format and log message with [`Level::Info`]
 #[macro_export]
 macro_rules! log_info {
+    (target: $target:expr, fields: { $($key:ident = $value:expr),* $(,)? }, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Info, $target, module_path!(), file!(), line!(), &[$(workflow_log::Field::new(stringify!($key), &$value)),*], &format_args!($($t)*))
+    );
+    (target: $target:expr, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Info, $target, module_path!(), file!(), line!(), &[], &format_args!($($t)*))
+    );
+    (fields: { $($key:ident = $value:expr),* $(,)? }, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Info, module_path!(), module_path!(), file!(), line!(), &[$(workflow_log::Field::new(stringify!($key), &$value)),*], &format_args!($($t)*))
+    );
     ($($t:tt)*) => (
-        workflow_log::impls::info_impl(&format_args!($($t)*))
+        workflow_log::impls::log_impl(workflow_log::Level::Info, module_path!(), module_path!(), file!(), line!(), &[], &format_args!($($t)*))
     )
 }
 
 /// Format and log message with [`Level::Debug`]
 #[macro_export]
 macro_rules! log_debug {
+    (target: $target:expr, fields: { $($key:ident = $value:expr),* $(,)? }, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Debug, $target, module_path!(), file!(), line!(), &[$(workflow_log::Field::new(stringify!($key), &$value)),*], &format_args!($($t)*))
+    );
+    (target: $target:expr, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Debug, $target, module_path!(), file!(), line!(), &[], &format_args!($($t)*))
+    );
+    (fields: { $($key:ident = $value:expr),* $(,)? }, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Debug, module_path!(), module_path!(), file!(), line!(), &[$(workflow_log::Field::new(stringify!($key), &$value)),*], &format_args!($($t)*))
+    );
     ($($t:tt)*) => (
-        workflow_log::impls::debug_impl(&format_args!($($t)*))
+        workflow_log::impls::log_impl(workflow_log::Level::Debug, module_path!(), module_path!(), file!(), line!(), &[], &format_args!($($t)*))
     )
 }
 
 /// Format and log message with [`Level::Trace`]
 #[macro_export]
 macro_rules! log_trace {
+    (target: $target:expr, fields: { $($key:ident = $value:expr),* $(,)? }, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Trace, $target, module_path!(), file!(), line!(), &[$(workflow_log::Field::new(stringify!($key), &$value)),*], &format_args!($($t)*))
+    );
+    (target: $target:expr, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Trace, $target, module_path!(), file!(), line!(), &[], &format_args!($($t)*))
+    );
+    (fields: { $($key:ident = $value:expr),* $(,)? }, $($t:tt)*) => (
+        workflow_log::impls::log_impl(workflow_log::Level::Trace, module_path!(), module_path!(), file!(), line!(), &[$(workflow_log::Field::new(stringify!($key), &$value)),*], &format_args!($($t)*))
+    );
     ($($t:tt)*) => (
-        workflow_log::impls::trace_impl(&format_args!($($t)*))
+        workflow_log::impls::log_impl(workflow_log::Level::Trace, module_path!(), module_path!(), file!(), line!(), &[], &format_args!($($t)*))
     )
 }
 
-use log_error;
-use log_warning;
-use log_info;
-use log_debug;
 use log_trace;
 
 /// Prints (using [`log_trace`]) a data slice
@@ -383,7 +937,7 @@ pub fn format_hex(data : &[u8]) -> String {
     .row_width(16)
     .finish();
 
-    format!("{}",view).into()
+    format!("{}",view)
 }
 
 /// Formats a hex data dump to contain color ranges
@@ -456,7 +1010,7 @@ pub mod color_log{
                 view = view.add_colors_with_range(colors);
             }
 
-            if let Err(_) = view.try_print(){
+            if view.try_print().is_err(){
                 trace_hex(&data_vec);
                 return Ok(false);
             }
@@ -468,3 +1022,345 @@ pub mod color_log{
 
 #[cfg(not(target_os = "solana"))]
 pub use color_log::*;
+
+#[cfg(all(test, not(target_os = "solana"), feature = "sink"))]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::{serial, CollectSink};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn to_sink(record : &Record<'_>) -> SinkOutput {
+        super::to_sink(record, record_consumers())
+    }
+
+    fn same(sink : &Arc<dyn Sink>, collector : &Arc<CollectSink>) -> bool {
+        std::ptr::addr_eq(Arc::as_ptr(sink), Arc::as_ptr(collector))
+    }
+
+    #[test]
+    fn sink_registry() {
+        let _serial = serial();
+        let first = CollectSink::new(true);
+        let second = CollectSink::new(true);
+        let first_id = add_sink(first.clone());
+        let second_id = add_sink(second.clone());
+        log_error!("both");
+        assert_eq!(first.texts(), ["both"]);
+        assert_eq!(second.texts(), ["both"]);
+
+        assert!(same(&remove_sink(first_id).unwrap(), &first));
+        assert!(remove_sink(first_id).is_none());
+        log_error!("second");
+        assert_eq!(first.texts(), ["both"]);
+        assert_eq!(second.texts(), ["both", "second"]);
+
+        let third = CollectSink::new(true);
+        pipe(Some(third.clone()));
+        assert!(remove_sink(second_id).is_none());
+        log_error!("third");
+        assert_eq!(second.texts(), ["both", "second"]);
+        assert_eq!(third.texts(), ["third"]);
+        pipe(None);
+    }
+
+    #[test]
+    fn consume_wins() {
+        let _serial = serial();
+        let consuming = CollectSink::new(true);
+        let passing = CollectSink::new(false);
+        let replacing = CollectSink::with_output(SinkOutput::Replace("replaced".into()));
+        pipe(Some(passing.clone()));
+        assert_eq!(to_sink(&Record::new(Level::Info, format_args!("first"))), SinkOutput::PassThrough);
+        add_sink(replacing.clone());
+        add_sink(CollectSink::with_output(SinkOutput::Escalate(Level::Error)));
+        assert_eq!(to_sink(&Record::new(Level::Info, format_args!("second"))), SinkOutput::Replace("replaced".into()));
+        add_sink(consuming.clone());
+        add_sink(passing.clone());
+        assert_eq!(to_sink(&Record::new(Level::Info, format_args!("third"))), SinkOutput::Consume);
+        assert_eq!(consuming.texts(), ["third"]);
+        assert_eq!(replacing.texts(), ["second", "third"]);
+        assert_eq!(passing.texts(), ["first", "second", "third", "third"]);
+        pipe(None);
+    }
+
+    #[test]
+    fn merge() {
+        use SinkOutput::*;
+        assert_eq!(PassThrough.merge(PassThrough), PassThrough);
+        assert_eq!(PassThrough.merge(Escalate(Level::Error)), Escalate(Level::Error));
+        assert_eq!(Replace("a".into()).merge(Escalate(Level::Error)), Replace("a".into()));
+        assert_eq!(Replace("a".into()).merge(Consume), Consume);
+        assert_eq!(Consume.merge(Replace("b".into())), Consume);
+    }
+
+    struct EmptySink;
+    impl Sink for EmptySink { }
+
+    struct CompatSink;
+    impl Sink for CompatSink {
+        fn write(&self, _level : Level, args : &fmt::Arguments<'_>) -> bool {
+            args.to_string() == "consume"
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn write_record_defaults_to_write() {
+        let record = Record::new(Level::Info, format_args!("consume"));
+        assert_eq!(CompatSink.write_record(&record), SinkOutput::Consume);
+        assert_eq!(CompatSink.write_record(&Record::new(Level::Info, format_args!("other"))), SinkOutput::PassThrough);
+        assert_eq!(EmptySink.write_record(&record), SinkOutput::PassThrough);
+        assert!(!EmptySink.write(Level::Info, &format_args!("text")));
+        assert_eq!(CompatSink.try_write_record(&record).unwrap(), SinkOutput::Consume);
+        assert_eq!(EmptySink.try_write_record(&record).unwrap(), SinkOutput::PassThrough);
+    }
+
+    /// Implements only `try_write_record`, failing for messages other than "consume".
+    struct FallibleSink;
+    impl Sink for FallibleSink {
+        fn try_write_record(&self, record : &Record<'_>) -> std::io::Result<SinkOutput> {
+            match record.args.to_string().as_str() {
+                "consume" => Ok(SinkOutput::Consume),
+                _ => Err(std::io::Error::other("failure"))
+            }
+        }
+    }
+
+    /// Implements only `write_record`, forwarding messages to the inner sink.
+    struct ForwardingSink(FallibleSink);
+    impl Sink for ForwardingSink {
+        fn write_record(&self, record : &Record<'_>) -> SinkOutput {
+            self.0.write_record(record)
+        }
+    }
+
+    /// Implements only `try_write_record`, forwarding messages to the
+    /// inner sink, which is located at the same address.
+    struct WrappingSink(FallibleSink);
+    impl Sink for WrappingSink {
+        fn try_write_record(&self, record : &Record<'_>) -> std::io::Result<SinkOutput> {
+            Ok(self.0.write_record(record))
+        }
+    }
+
+    #[test]
+    fn write_record_defaults_to_try_write_record() {
+        let record = Record::new(Level::Info, format_args!("consume"));
+        assert_eq!(FallibleSink.write_record(&record), SinkOutput::Consume);
+        assert_eq!(FallibleSink.write_record(&Record::new(Level::Info, format_args!("other"))), SinkOutput::PassThrough);
+        assert_eq!(ForwardingSink(FallibleSink).try_write_record(&record).unwrap(), SinkOutput::Consume);
+        assert_eq!(ForwardingSink(FallibleSink).write_record(&record), SinkOutput::Consume);
+        assert_eq!(WrappingSink(FallibleSink).write_record(&record), SinkOutput::Consume);
+    }
+
+    struct ErrorSink;
+    impl Sink for ErrorSink {
+        fn enabled(&self, level : Level) -> bool {
+            level == Level::Error
+        }
+    }
+
+    struct NetSink;
+    impl Sink for NetSink {
+        fn enabled_for(&self, _level : Level, target : &str) -> bool {
+            target.starts_with("net")
+        }
+    }
+
+    #[test]
+    fn sink_target_filters() {
+        let mut handler = SinkHandler::new(Arc::new(EmptySink));
+        handler.level_filter = LevelFilter::Warn;
+        handler.set_target_level("app", LevelFilter::Info);
+        handler.set_target_level("app::db", LevelFilter::Trace);
+        handler.set_target_level("app::ui", LevelFilter::Off);
+        assert!(handler.enabled(Level::Warn, "other"));
+        assert!(!handler.enabled(Level::Info, "other"));
+        assert!(handler.enabled(Level::Info, "app::net"));
+        assert!(!handler.enabled(Level::Debug, "app::net"));
+        assert!(handler.enabled(Level::Trace, "app::db::pool"));
+        assert!(!handler.enabled(Level::Error, "app::ui"));
+        handler.set_target_level("app::ui", LevelFilter::Error);
+        assert!(handler.enabled(Level::Error, "app::ui"));
+
+        assert!(SinkHandler::new(Arc::new(ErrorSink)).enabled(Level::Error, "app"));
+        assert!(!SinkHandler::new(Arc::new(ErrorSink)).enabled(Level::Warn, "app"));
+        assert!(SinkHandler::new(Arc::new(NetSink)).enabled(Level::Info, "net::tcp"));
+        assert!(!SinkHandler::new(Arc::new(NetSink)).enabled(Level::Info, "app"));
+    }
+
+    /// Counts the lifecycle notifications it receives.
+    #[derive(Default)]
+    struct LifecycleSink {
+        installed : AtomicUsize,
+        removed : AtomicUsize,
+        flushed : AtomicUsize,
+    }
+
+    impl LifecycleSink {
+        fn counts(&self) -> (usize, usize, usize) {
+            (self.installed.load(Ordering::SeqCst), self.removed.load(Ordering::SeqCst), self.flushed.load(Ordering::SeqCst))
+        }
+    }
+
+    impl Sink for LifecycleSink {
+        fn flush(&self) {
+            self.flushed.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_install(&self) {
+            self.installed.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_remove(&self) {
+            self.removed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn sink_lifecycle() {
+        let _serial = serial();
+        let first = Arc::new(LifecycleSink::default());
+        let second = Arc::new(LifecycleSink::default());
+        let first_id = add_sink(first.clone());
+        add_sink(second.clone());
+        assert_eq!(first.counts(), (1, 0, 0));
+        flush();
+        assert_eq!(first.counts(), (1, 0, 1));
+        assert_eq!(second.counts(), (1, 0, 1));
+
+        remove_sink(first_id);
+        assert_eq!(first.counts(), (1, 1, 1));
+        flush();
+        assert_eq!(first.counts(), (1, 1, 1));
+        assert_eq!(second.counts(), (1, 0, 2));
+
+        pipe(Some(first.clone()));
+        assert_eq!(first.counts(), (2, 1, 1));
+        assert_eq!(second.counts(), (1, 1, 2));
+        pipe(None);
+        assert_eq!(first.counts(), (2, 2, 1));
+        flush();
+        assert_eq!(first.counts(), (2, 2, 1));
+    }
+
+    fn log(text : &str) {
+        impls::record_impl(&Record::new(Level::Error, format_args!("{}", text)));
+    }
+
+    #[test]
+    fn with_sink_nesting() {
+        let _serial = serial();
+        let outer = CollectSink::new(true);
+        let inner = CollectSink::new(true);
+        let result = with_sink(outer.clone(), || {
+            log("outer 1");
+            let result = with_sink(inner.clone(), || {
+                log("inner");
+                1
+            });
+            log("outer 2");
+            result + 1
+        });
+        assert_eq!(result, 2);
+        assert_eq!(outer.texts(), ["outer 1", "outer 2"]);
+        assert_eq!(inner.texts(), ["inner"]);
+        assert_eq!(to_sink(&Record::new(Level::Error, format_args!("after"))), SinkOutput::PassThrough);
+        assert_eq!(outer.texts().len(), 2);
+    }
+
+    #[test]
+    fn with_sink_scope() {
+        let _serial = serial();
+        let outer = CollectSink::new(true);
+        let inner = CollectSink::new(true);
+        with_sink(outer.clone(), || {
+            // the scope is restored if the closure panics
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                with_sink(inner.clone(), || panic!("failure"))
+            }));
+            assert!(result.is_err());
+            log("after panic");
+
+            // messages from other threads are not delivered to the scoped sink
+            std::thread::spawn(|| {
+                assert_eq!(to_sink(&Record::new(Level::Error, format_args!("other thread"))), SinkOutput::PassThrough);
+            }).join().unwrap();
+
+            // the scoped sink receives messages in place of the global sinks
+            assert_eq!(to_sink(&Record::new(Level::Error, format_args!("consumed"))), SinkOutput::Consume);
+        });
+        assert_eq!(outer.texts(), ["after panic", "consumed"]);
+        assert!(inner.texts().is_empty());
+
+        // the scoped sink is only sent messages it is interested in
+        let disabled = CollectSink::with_level(Some(SinkOutput::Consume), Level::Error);
+        with_sink(disabled.clone(), || {
+            assert_eq!(to_sink(&Record::new(Level::Warn, format_args!("message"))), SinkOutput::PassThrough);
+        });
+        assert!(disabled.texts().is_empty());
+
+        let net = Arc::new(NetCollector(CollectSink::new(true)));
+        with_sink(net.clone(), || {
+            assert_eq!(to_sink(&Record { target : "app", ..Record::new(Level::Error, format_args!("app")) }), SinkOutput::PassThrough);
+            assert_eq!(to_sink(&Record { target : "net::tcp", ..Record::new(Level::Error, format_args!("net")) }), SinkOutput::Consume);
+        });
+        assert_eq!(net.0.texts(), ["net"]);
+    }
+
+    /// Collects the messages of targets starting with `net`.
+    struct NetCollector(Arc<CollectSink>);
+    impl Sink for NetCollector {
+        fn write_record(&self, record : &Record<'_>) -> SinkOutput {
+            self.0.write_record(record)
+        }
+
+        fn enabled_for(&self, _level : Level, target : &str) -> bool {
+            target.starts_with("net")
+        }
+    }
+
+    /// Logs, registers a sink and flushes the sinks from within `write_record`.
+    struct ReentrantSink {
+        collector : Arc<CollectSink>,
+        added : Arc<CollectSink>,
+        added_id : std::sync::Mutex<Option<SinkId>>,
+    }
+
+    impl Sink for ReentrantSink {
+        fn write_record(&self, record : &Record<'_>) -> SinkOutput {
+            self.collector.write_record(record);
+            log_error!("nested");
+            let mut added_id = self.added_id.lock().unwrap();
+            if added_id.is_none() {
+                *added_id = Some(add_sink(self.added.clone()));
+            }
+            flush();
+            SinkOutput::Consume
+        }
+    }
+
+    #[test]
+    fn reentrant_sink() {
+        let _serial = serial();
+        let sink = Arc::new(ReentrantSink {
+            collector : CollectSink::new(true),
+            added : CollectSink::new(true),
+            added_id : Default::default(),
+        });
+        add_sink(sink.clone());
+
+        // the nested message is output to the console only
+        let (_, logs) = crate::capture_logs(|| log_error!("outer"));
+        assert_eq!(logs.len(), 1);
+        assert!(logs.contains(Level::Error, "outer"));
+        assert_eq!(sink.collector.texts(), ["outer"]);
+        assert!(sink.added.texts().is_empty());
+
+        // the sink registered from within the sink receives later messages
+        log_error!("after");
+        assert_eq!(sink.collector.texts(), ["outer", "after"]);
+        assert_eq!(sink.added.texts(), ["after"]);
+        pipe(None);
+    }
+}
@@ -0,0 +1,88 @@
+//!
+//! Panic hook routing panics through the log subsystem.
+//!
+
+use crate::impls;
+use std::sync::Once;
+
+static INSTALL : Once = Once::new();
+
+/// Installs a panic hook that logs panics as [`Level::Error`](crate::Level::Error)
+/// messages, so that registered sinks (files, ring buffers etc.) record crashes.
+///
+/// The message contains the panic message, its location and, on native
+/// platforms, a backtrace. Unless consumed by a sink, it is output to the
+/// console (`console.error()` under WASM). All registered sinks are then
+/// flushed, unless the panic has occurred within a sink, and the previously
+/// installed panic hook is invoked.
+///
+/// Subsequent calls have no effect.
+pub fn install_panic_hook() {
+    INSTALL.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let payload = info.payload();
+            let message = payload.downcast_ref::<&str>().copied()
+                .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                .unwrap_or("Box<dyn Any>");
+            let location = info.location()
+                .map(|location| format!(" at {}:{}:{}", location.file(), location.line(), location.column()))
+                .unwrap_or_default();
+
+            #[cfg(not(target_arch = "wasm32"))] {
+                let thread = std::thread::current();
+                let backtrace = std::backtrace::Backtrace::force_capture();
+                impls::error_impl(&format_args!("thread '{}' panicked{}:\n{}\nstack backtrace:\n{}", thread.name().unwrap_or("<unnamed>"), location, message, backtrace));
+            }
+            #[cfg(target_arch = "wasm32")]
+            impls::error_impl(&format_args!("panicked{}:\n{}", location, message));
+
+            // sinks can not be flushed from within a sink as they may be
+            // holding their own locks or waiting for the panicking thread
+            #[cfg(feature = "sink")]
+            if !crate::in_sink() {
+                crate::flush();
+            }
+
+            previous(info);
+        }));
+    });
+}
+
+#[cfg(all(test, feature = "sink"))]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::{serial, CollectSink};
+    use crate::{with_sink, Level};
+    use std::cell::Cell;
+    use std::panic::{catch_unwind, set_hook, take_hook};
+
+    thread_local! {
+        static CHAINED : Cell<bool> = const { Cell::new(false) };
+    }
+
+    #[test]
+    fn panic_hook() {
+        let _serial = serial();
+        let original = take_hook();
+        set_hook(Box::new(|_| CHAINED.with(|chained| chained.set(true))));
+        install_panic_hook();
+
+        let collector = CollectSink::new(true);
+        let line = line!() + 2;
+        let result = catch_unwind(|| with_sink(collector.clone(), || {
+            panic!("failure");
+        }));
+        assert!(result.is_err());
+
+        let entries = collector.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].level, Level::Error);
+        assert!(entries[0].text.contains("failure"));
+        assert!(entries[0].text.contains(&format!("{}:{}", file!(), line)));
+        assert!(CHAINED.with(Cell::get));
+
+        drop(take_hook());
+        set_hook(original);
+    }
+}
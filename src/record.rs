@@ -0,0 +1,91 @@
+use crate::{system_time, Field, Level};
+use std::fmt;
+use std::time::SystemTime;
+
+/// A log message along with its metadata, delivered to sinks
+/// via [`Sink::write_record`](crate::Sink::write_record).
+///
+/// The log macros fill in the target (the module path unless
+/// specified using `target:`), the module path, file and line
+/// of the log statement, along with any structured fields supplied
+/// using `fields:`. Messages logged without this information
+/// (e.g. using [`impls::info_impl`](crate::impls::info_impl)) have
+/// an empty target and no location.
+#[derive(Clone, Debug)]
+pub struct Record<'a> {
+    pub level : Level,
+    pub args : fmt::Arguments<'a>,
+    pub target : &'a str,
+    pub module_path : Option<&'a str>,
+    pub file : Option<&'a str>,
+    pub line : Option<u32>,
+    pub timestamp : SystemTime,
+    pub fields : &'a [Field<'a>],
+}
+
+impl<'a> Record<'a> {
+    /// Creates a record without location information, timestamped with the current time.
+    pub fn new(level : Level, args : fmt::Arguments<'a>) -> Record<'a> {
+        Record {
+            level,
+            args,
+            target : "",
+            module_path : None,
+            file : None,
+            line : None,
+            timestamp : system_time(),
+            fields : &[],
+        }
+    }
+}
+
+/// An owned copy of a [`Record`] with the message formatted
+/// into a string, used where records need to be retained.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub level : Level,
+    pub text : String,
+    pub target : String,
+    pub module_path : Option<String>,
+    pub file : Option<String>,
+    pub line : Option<u32>,
+    pub timestamp : SystemTime,
+    /// Structured fields with their values formatted into strings.
+    pub fields : Vec<(String, String)>,
+}
+
+impl LogEntry {
+    /// Invokes `f` with a [`Record`] borrowing the contents of this entry.
+    pub fn with_record<R>(&self, f : impl FnOnce(&Record<'_>) -> R) -> R {
+        let fields = self.fields.iter()
+            .map(|(key, value)| Field::new(key, value))
+            .collect::<Vec<_>>();
+        f(&Record {
+            level : self.level,
+            args : format_args!("{}", self.text),
+            target : &self.target,
+            module_path : self.module_path.as_deref(),
+            file : self.file.as_deref(),
+            line : self.line,
+            timestamp : self.timestamp,
+            fields : &fields,
+        })
+    }
+}
+
+impl<'a> From<&Record<'a>> for LogEntry {
+    fn from(record : &Record<'a>) -> LogEntry {
+        LogEntry {
+            level : record.level,
+            text : record.args.to_string(),
+            target : record.target.to_string(),
+            module_path : record.module_path.map(String::from),
+            file : record.file.map(String::from),
+            line : record.line,
+            timestamp : record.timestamp,
+            fields : record.fields.iter()
+                .map(|field| (field.key.to_string(), field.value.to_string()))
+                .collect(),
+        }
+    }
+}
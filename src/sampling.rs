@@ -0,0 +1,168 @@
+//!
+//! Sampling of high-volume log levels.
+//!
+//! A [`Sampling`] policy can be assigned to each log level using
+//! [`set_sampling`]. Messages discarded by sampling are dropped before
+//! they are formatted or delivered to sinks; log capture is not affected.
+//!
+
+use crate::Level;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+
+/// Determines which messages of a log level are retained.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampling {
+    /// Retain all messages.
+    All,
+    /// Retain each message with the given probability (`0.0..=1.0`).
+    Probability(f64),
+    /// Retain the first message and every n-th message after it.
+    EveryNth(u64),
+}
+
+const MODE_ALL : u8 = 0;
+const MODE_PROBABILITY : u8 = 1;
+const MODE_EVERY_NTH : u8 = 2;
+
+const GAMMA : u64 = 0x9e3779b97f4a7c15;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const MODE_INIT : AtomicU8 = AtomicU8::new(MODE_ALL);
+#[allow(clippy::declare_interior_mutable_const)]
+const U64_INIT : AtomicU64 = AtomicU64::new(0);
+
+static MODES : [AtomicU8; 5] = [MODE_INIT; 5];
+static PARAMS : [AtomicU64; 5] = [U64_INIT; 5];
+static COUNTERS : [AtomicU64; 5] = [U64_INIT; 5];
+static RNG_STATE : AtomicU64 = AtomicU64::new(GAMMA);
+
+#[inline(always)]
+fn index(level : Level) -> usize {
+    level as usize - 1
+}
+
+/// Assigns a sampling policy to the given log level.
+pub fn set_sampling(level : Level, sampling : Sampling) {
+    let index = index(level);
+    let (mode, param) = match sampling {
+        Sampling::All => (MODE_ALL, 0),
+        Sampling::Probability(p) if p >= 1.0 => (MODE_ALL, 0),
+        Sampling::Probability(p) => (MODE_PROBABILITY, (p.max(0.0) * u64::MAX as f64) as u64),
+        Sampling::EveryNth(n) => (MODE_EVERY_NTH, n.max(1)),
+    };
+    PARAMS[index].store(param, Ordering::Relaxed);
+    COUNTERS[index].store(0, Ordering::Relaxed);
+    MODES[index].store(mode, Ordering::Release);
+}
+
+/// Returns the sampling policy assigned to the given log level.
+pub fn sampling(level : Level) -> Sampling {
+    let index = index(level);
+    let param = PARAMS[index].load(Ordering::Relaxed);
+    match MODES[index].load(Ordering::Acquire) {
+        MODE_PROBABILITY => Sampling::Probability(param as f64 / u64::MAX as f64),
+        MODE_EVERY_NTH => Sampling::EveryNth(param),
+        _ => Sampling::All,
+    }
+}
+
+/// Seeds the random number generator used for probabilistic sampling
+/// and resets the counters used by [`Sampling::EveryNth`], making
+/// sampling decisions reproducible.
+pub fn set_sampling_seed(seed : u64) {
+    RNG_STATE.store(seed, Ordering::Relaxed);
+    for counter in COUNTERS.iter() {
+        counter.store(0, Ordering::Relaxed);
+    }
+}
+
+/// SplitMix64 generator advanced atomically, see
+/// <https://prng.di.unimi.it/splitmix64.c>
+fn next_random() -> u64 {
+    let mut z = RNG_STATE.fetch_add(GAMMA, Ordering::Relaxed).wrapping_add(GAMMA);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// Returns `true` if a message of the given level should be retained.
+#[inline(always)]
+pub(crate) fn sampled(level : Level) -> bool {
+    let index = index(level);
+    match MODES[index].load(Ordering::Acquire) {
+        MODE_ALL => true,
+        MODE_PROBABILITY => next_random() < PARAMS[index].load(Ordering::Relaxed),
+        _ => COUNTERS[index].fetch_add(1, Ordering::Relaxed).is_multiple_of(PARAMS[index].load(Ordering::Relaxed)),
+    }
+}
+
+#[cfg(all(test, feature = "sink"))]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::{serial, CollectSink};
+    use crate::{add_sink, capture_logs, log_trace, remove_sink};
+
+    fn sequence(level : Level, count : usize) -> Vec<bool> {
+        (0..count).map(|_| sampled(level)).collect()
+    }
+
+    #[test]
+    fn seeded_probability() {
+        let _serial = serial();
+        set_sampling(Level::Trace, Sampling::Probability(0.5));
+        set_sampling_seed(42);
+        let first = sequence(Level::Trace, 64);
+        set_sampling_seed(42);
+        assert_eq!(sequence(Level::Trace, 64), first);
+        let retained = first.iter().filter(|retained| **retained).count();
+        assert!(retained > 16 && retained < 48);
+        set_sampling(Level::Trace, Sampling::All);
+    }
+
+    #[test]
+    fn every_nth() {
+        let _serial = serial();
+        set_sampling(Level::Trace, Sampling::EveryNth(3));
+        assert_eq!(sequence(Level::Trace, 7), [true, false, false, true, false, false, true]);
+        // the seed resets the counters
+        set_sampling_seed(0);
+        assert_eq!(sequence(Level::Trace, 2), [true, false]);
+        set_sampling(Level::Trace, Sampling::All);
+    }
+
+    #[test]
+    fn clamping() {
+        let _serial = serial();
+        for (policy, clamped) in [
+            (Sampling::Probability(1.0), Sampling::All),
+            (Sampling::Probability(1.5), Sampling::All),
+            (Sampling::Probability(0.0), Sampling::Probability(0.0)),
+            (Sampling::Probability(-1.0), Sampling::Probability(0.0)),
+            (Sampling::Probability(f64::NAN), Sampling::Probability(0.0)),
+            (Sampling::EveryNth(0), Sampling::EveryNth(1)),
+        ] {
+            set_sampling(Level::Trace, policy);
+            assert_eq!(sampling(Level::Trace), clamped);
+            let expected = clamped != Sampling::Probability(0.0);
+            assert_eq!(sequence(Level::Trace, 16), [expected; 16]);
+        }
+        set_sampling(Level::Trace, Sampling::All);
+    }
+
+    #[test]
+    fn sampled_out_messages_are_captured() {
+        let _serial = serial();
+        let sink = CollectSink::new(true);
+        let id = add_sink(sink.clone());
+        set_sampling(Level::Trace, Sampling::EveryNth(2));
+        let (_, logs) = capture_logs(|| {
+            for index in 0..4 {
+                log_trace!("message {}", index);
+            }
+        });
+        assert_eq!(sink.texts(), ["message 0", "message 2"]);
+        assert_eq!(logs.len(), 4);
+        remove_sink(id);
+        set_sampling(Level::Trace, Sampling::All);
+    }
+}
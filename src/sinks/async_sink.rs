@@ -0,0 +1,457 @@
+use crate::{Level, LogEntry, Record, Sink, SinkOutput};
+use crate::sync::{lock, wait};
+use std::collections::VecDeque;
+use std::io;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{JoinHandle, ThreadId};
+
+/// Determines what [`AsyncSink`] does with a new message
+/// when its queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Block the logging thread until the queue has room.
+    Block,
+    /// Discard the new message.
+    DropNewest,
+    /// Discard the oldest queued message to make room for the new one.
+    DropOldest,
+}
+
+struct State {
+    records : VecDeque<LogEntry>,
+    busy : bool,
+    shutdown : bool,
+    /// The worker thread has exited
+    stopped : bool,
+    dropped : u64,
+}
+
+struct Shared {
+    sink : Arc<dyn Sink>,
+    capacity : usize,
+    policy : OverflowPolicy,
+    state : Mutex<State>,
+    /// Signalled when records are queued or shutdown is requested
+    pending : Condvar,
+    /// Signalled when the worker removes records from the queue
+    available : Condvar,
+    /// Signalled when the worker has processed all queued records
+    drained : Condvar,
+}
+
+/// Marks the worker as stopped when the worker thread exits,
+/// waking any threads waiting for it.
+struct StoppedGuard<'a>(&'a Shared);
+
+impl Drop for StoppedGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.busy = false;
+        state.stopped = true;
+        self.0.available.notify_all();
+        self.0.drained.notify_all();
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+
+    /// Delivers a message to the wrapped sink. A panic in the wrapped sink
+    /// is contained, so that it does not stop the worker thread.
+    fn deliver(&self, entry : &LogEntry) {
+        let _ = catch_unwind(AssertUnwindSafe(|| entry.with_record(|record| self.sink.write_record(record))));
+    }
+
+    /// Delivers a message on the logging thread, preceded by
+    /// any messages left in the queue by a stopped worker.
+    fn write_direct(&self, mut state : MutexGuard<'_, State>, record : &Record<'_>) -> io::Result<SinkOutput> {
+        let remaining = if state.stopped { std::mem::take(&mut state.records) } else { VecDeque::new() };
+        drop(state);
+        for entry in remaining.iter() {
+            self.deliver(entry);
+        }
+        self.sink.try_write_record(record).map(|_| SinkOutput::PassThrough)
+    }
+
+    fn run(&self) {
+        let _guard = StoppedGuard(self);
+        loop {
+            let batch = {
+                let mut state = self.lock();
+                while state.records.is_empty() && !state.shutdown {
+                    state = wait(&self.pending, state);
+                }
+                if state.records.is_empty() {
+                    break;
+                }
+                state.busy = true;
+                self.available.notify_all();
+                std::mem::take(&mut state.records)
+            };
+
+            for entry in batch.iter() {
+                self.deliver(entry);
+            }
+
+            let mut state = self.lock();
+            state.busy = false;
+            if state.records.is_empty() {
+                self.drained.notify_all();
+            }
+        }
+    }
+}
+
+/// A [`Sink`] wrapper that delivers messages to the wrapped sink
+/// from a dedicated worker thread, so that a slow sink does not
+/// stall the threads that are logging.
+///
+/// Messages are formatted on the logging thread and placed into a
+/// bounded queue drained by the worker. When the queue is full, the
+/// configured [`OverflowPolicy`] is applied.
+///
+/// As messages are delivered asynchronously, the result returned by
+/// the wrapped sink is ignored and `AsyncSink` never consumes messages.
+/// Panics of the wrapped sink are contained by the worker thread; should
+/// the worker thread exit nonetheless, messages are delivered to the
+/// wrapped sink synchronously on the logging thread.
+///
+/// Sinks registered globally are never dropped, so applications should
+/// call [`flush`](crate::flush) or [`AsyncSink::shutdown`] before exiting
+/// to make sure all queued messages are delivered.
+pub struct AsyncSink {
+    shared : Arc<Shared>,
+    worker : Mutex<Option<JoinHandle<()>>>,
+    worker_id : ThreadId,
+}
+
+impl AsyncSink {
+    /// Creates a new `AsyncSink` delivering messages to `sink` and spawns
+    /// its worker thread. `capacity` is the maximum number of queued messages.
+    pub fn new(sink : Arc<dyn Sink>, capacity : usize, policy : OverflowPolicy) -> AsyncSink {
+        let shared = Arc::new(Shared {
+            sink,
+            capacity : capacity.max(1),
+            policy,
+            state : Mutex::new(State {
+                records : VecDeque::new(),
+                busy : false,
+                shutdown : false,
+                stopped : false,
+                dropped : 0,
+            }),
+            pending : Condvar::new(),
+            available : Condvar::new(),
+            drained : Condvar::new(),
+        });
+
+        let worker = {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name("workflow-log-async-sink".to_string())
+                .spawn(move || crate::in_sink_scope(|| shared.run()))
+                .expect("unable to spawn async sink worker thread")
+        };
+
+        AsyncSink {
+            shared,
+            worker_id : worker.thread().id(),
+            worker : Mutex::new(Some(worker)),
+        }
+    }
+
+    /// Returns `true` if invoked by the wrapped sink on the worker thread.
+    fn on_worker(&self) -> bool {
+        std::thread::current().id() == self.worker_id
+    }
+
+    /// Returns the number of messages discarded due to the queue being full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+
+    /// Blocks until all queued messages have been delivered to the wrapped
+    /// sink and then flushes the wrapped sink. Returns immediately if invoked
+    /// by the wrapped sink (e.g. by calling [`flush`](crate::flush) from
+    /// [`Sink::write_record`]), as the worker thread can not wait for itself.
+    pub fn flush(&self) {
+        if self.on_worker() {
+            return;
+        }
+        let remaining = {
+            let mut state = self.shared.lock();
+            while (!state.records.is_empty() || state.busy) && !state.stopped {
+                state = wait(&self.shared.drained, state);
+            }
+            std::mem::take(&mut state.records)
+        };
+        // queued messages left behind by a stopped worker
+        for entry in remaining.iter() {
+            self.shared.deliver(entry);
+        }
+        self.shared.sink.flush();
+    }
+
+    /// Delivers all queued messages and stops the worker thread.
+    /// Messages received after shutdown are delivered to the wrapped
+    /// sink synchronously on the logging thread. If invoked by the
+    /// wrapped sink, the worker thread stops without being waited for.
+    pub fn shutdown(&self) {
+        self.shared.lock().shutdown = true;
+        self.shared.pending.notify_all();
+        self.shared.available.notify_all();
+        if self.on_worker() {
+            return;
+        }
+        if let Some(worker) = lock(&self.worker).take() {
+            worker.join().ok();
+        }
+    }
+}
+
+impl Sink for AsyncSink {
+    /// Reports failures of the wrapped sink only for messages delivered
+    /// synchronously (after shutdown); failures to process queued messages
+    /// occur on the worker thread and can not be reported.
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        let shared = &self.shared;
+        // formatted before taking the lock, so that formatting does not block other threads
+        let entry = LogEntry::from(record);
+        let mut state = shared.lock();
+        if state.shutdown || state.stopped {
+            return shared.write_direct(state, record);
+        }
+
+        if state.records.len() >= shared.capacity {
+            match shared.policy {
+                OverflowPolicy::Block => {
+                    while state.records.len() >= shared.capacity && !state.shutdown && !state.stopped {
+                        state = wait(&shared.available, state);
+                    }
+                    if state.shutdown || state.stopped {
+                        return shared.write_direct(state, record);
+                    }
+                },
+                OverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    return Ok(SinkOutput::PassThrough);
+                },
+                OverflowPolicy::DropOldest => {
+                    state.records.pop_front();
+                    state.dropped += 1;
+                }
+            }
+        }
+
+        state.records.push_back(entry);
+        shared.pending.notify_one();
+        Ok(SinkOutput::PassThrough)
+    }
+
+    fn enabled(&self, level : Level) -> bool {
+        self.shared.sink.enabled(level)
+    }
+
+    fn enabled_for(&self, level : Level, target : &str) -> bool {
+        self.shared.sink.enabled_for(level, target)
+    }
+
+    fn flush(&self) {
+        AsyncSink::flush(self);
+    }
+
+    fn on_install(&self) {
+        self.shared.sink.on_install();
+    }
+
+    /// Delivers all queued messages before notifying the wrapped sink.
+    fn on_remove(&self) {
+        AsyncSink::flush(self);
+        self.shared.sink.on_remove();
+    }
+}
+
+impl Drop for AsyncSink {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::{serial, write, CollectSink};
+    use std::time::Duration;
+
+    /// Panics on messages containing `panic`, forwarding other messages.
+    struct PanickingSink(Arc<CollectSink>);
+
+    impl Sink for PanickingSink {
+        fn write_record(&self, record : &Record<'_>) -> SinkOutput {
+            if record.args.to_string().contains("panic") {
+                panic!("sink failure");
+            }
+            self.0.write_record(record)
+        }
+    }
+
+    #[test]
+    fn panicking_sink_does_not_stop_worker() {
+        let collector = CollectSink::new(false);
+        let sink = AsyncSink::new(Arc::new(PanickingSink(collector.clone())), 1, OverflowPolicy::Block);
+        for text in ["one", "panic", "two", "panic", "three"] {
+            write(&sink, text);
+        }
+        sink.flush();
+        assert_eq!(collector.texts(), ["one", "two", "three"]);
+        write(&sink, "four");
+        sink.flush();
+        assert_eq!(collector.texts(), ["one", "two", "three", "four"]);
+    }
+
+    /// Holds messages until opened, then forwards them.
+    struct GateSink {
+        collector : Arc<CollectSink>,
+        open : Mutex<bool>,
+        opened : Condvar,
+    }
+
+    impl GateSink {
+        fn new(collector : Arc<CollectSink>) -> Arc<GateSink> {
+            Arc::new(GateSink { collector, open : Mutex::new(false), opened : Condvar::new() })
+        }
+
+        fn open(&self) {
+            *self.open.lock().unwrap() = true;
+            self.opened.notify_all();
+        }
+    }
+
+    impl Sink for GateSink {
+        fn write_record(&self, record : &Record<'_>) -> SinkOutput {
+            let mut open = self.open.lock().unwrap();
+            while !*open {
+                open = self.opened.wait(open).unwrap();
+            }
+            self.collector.write_record(record)
+        }
+    }
+
+    /// Fills the queue of a sink with capacity 2 while the worker is
+    /// held delivering the first message, then writes a fourth message.
+    fn overflow(policy : OverflowPolicy) -> (Vec<String>, u64) {
+        let collector = CollectSink::new(false);
+        let gate = GateSink::new(collector.clone());
+        let sink = Arc::new(AsyncSink::new(gate.clone(), 2, policy));
+        write(&*sink, "one");
+        while !sink.shared.lock().busy {
+            std::thread::yield_now();
+        }
+        write(&*sink, "two");
+        write(&*sink, "three");
+        if policy == OverflowPolicy::Block {
+            let writer = {
+                let sink = sink.clone();
+                std::thread::spawn(move || { write(&*sink, "four"); })
+            };
+            std::thread::sleep(Duration::from_millis(50));
+            assert!(!writer.is_finished());
+            assert_eq!(sink.shared.lock().records.len(), 2);
+            gate.open();
+            writer.join().unwrap();
+        } else {
+            write(&*sink, "four");
+            gate.open();
+        }
+        sink.flush();
+        (collector.texts(), sink.dropped())
+    }
+
+    #[test]
+    fn overflow_block() {
+        assert_eq!(overflow(OverflowPolicy::Block), (vec!["one".into(), "two".into(), "three".into(), "four".into()], 0));
+    }
+
+    #[test]
+    fn overflow_drop_newest() {
+        assert_eq!(overflow(OverflowPolicy::DropNewest), (vec!["one".into(), "two".into(), "three".into()], 1));
+    }
+
+    #[test]
+    fn overflow_drop_oldest() {
+        assert_eq!(overflow(OverflowPolicy::DropOldest), (vec!["one".into(), "three".into(), "four".into()], 1));
+    }
+
+    #[test]
+    fn shutdown_delivers_queued_messages() {
+        let collector = CollectSink::new(false);
+        let gate = GateSink::new(collector.clone());
+        let sink = AsyncSink::new(gate.clone(), 10, OverflowPolicy::Block);
+        for text in ["one", "two", "three"] {
+            assert_eq!(write(&sink, text), SinkOutput::PassThrough);
+        }
+        assert!(collector.texts().is_empty());
+        gate.open();
+        sink.shutdown();
+        assert_eq!(collector.texts(), ["one", "two", "three"]);
+        write(&sink, "four");
+        assert_eq!(collector.texts(), ["one", "two", "three", "four"]);
+    }
+
+    #[test]
+    fn direct_delivery_reports_failures() {
+        let sink = AsyncSink::new(CollectSink::failing(), 2, OverflowPolicy::Block);
+        assert!(sink.try_write_record(&Record::new(Level::Info, format_args!("queued"))).is_ok());
+        sink.shutdown();
+        assert!(sink.try_write_record(&Record::new(Level::Info, format_args!("direct"))).is_err());
+    }
+
+    #[test]
+    fn stopped_worker_falls_back_to_direct_delivery() {
+        let collector = CollectSink::new(false);
+        let sink = AsyncSink::new(collector.clone(), 2, OverflowPolicy::Block);
+        sink.shutdown();
+        assert!(sink.shared.lock().stopped);
+        // messages left in the queue are delivered ahead of the new message
+        sink.shared.lock().records.push_back(LogEntry::from(&Record::new(Level::Info, format_args!("queued"))));
+        write(&sink, "direct");
+        assert_eq!(collector.texts(), ["queued", "direct"]);
+        sink.shared.lock().records.push_back(LogEntry::from(&Record::new(Level::Info, format_args!("flushed"))));
+        sink.flush();
+        assert_eq!(collector.texts(), ["queued", "direct", "flushed"]);
+    }
+
+    /// Flushes all registered sinks while processing a message.
+    struct FlushingSink(Arc<CollectSink>);
+
+    impl Sink for FlushingSink {
+        fn write_record(&self, record : &Record<'_>) -> SinkOutput {
+            crate::flush();
+            self.0.write_record(record)
+        }
+    }
+
+    #[test]
+    fn flush_from_wrapped_sink() {
+        let _serial = serial();
+        let collector = CollectSink::new(false);
+        let sink = Arc::new(AsyncSink::new(Arc::new(FlushingSink(collector.clone())), 2, OverflowPolicy::Block));
+        let id = crate::add_sink(sink.clone());
+        write(&*sink, "one");
+        write(&*sink, "two");
+        let (sender, receiver) = std::sync::mpsc::channel();
+        {
+            let sink = sink.clone();
+            std::thread::spawn(move || {
+                sink.flush();
+                sender.send(()).unwrap();
+            });
+        }
+        receiver.recv_timeout(Duration::from_secs(10)).expect("flush blocked");
+        assert_eq!(collector.texts(), ["one", "two"]);
+        crate::remove_sink(id);
+    }
+}
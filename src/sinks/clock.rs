@@ -0,0 +1,16 @@
+use std::time::SystemTime;
+
+/// Source of the current time used by time-dependent sinks.
+/// A custom clock can be supplied to simulate the passage of time.
+pub trait Clock : Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+/// [`Clock`] returning the system time.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        crate::system_time()
+    }
+}
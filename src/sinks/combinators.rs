@@ -0,0 +1,291 @@
+use crate::{Level, Record, Sink, SinkOutput};
+use std::io;
+use std::sync::Arc;
+
+/// Delivers messages to two sinks. Their results are
+/// combined using [`SinkOutput::merge`].
+pub struct Tee {
+    first : Arc<dyn Sink>,
+    second : Arc<dyn Sink>,
+}
+
+impl Tee {
+    pub fn new(first : Arc<dyn Sink>, second : Arc<dyn Sink>) -> Tee {
+        Tee { first, second }
+    }
+}
+
+impl Sink for Tee {
+    /// Fails only if both sinks fail to process the message.
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        let first = deliver(&self.first, record);
+        let second = deliver(&self.second, record);
+        match (first, second) {
+            (Err(err), Err(_)) => Err(err),
+            (first, second) => Ok(first.unwrap_or(SinkOutput::PassThrough).merge(second.unwrap_or(SinkOutput::PassThrough)))
+        }
+    }
+
+    fn enabled(&self, level : Level) -> bool {
+        self.first.enabled(level) || self.second.enabled(level)
+    }
+
+    fn enabled_for(&self, level : Level, target : &str) -> bool {
+        self.first.enabled_for(level, target) || self.second.enabled_for(level, target)
+    }
+
+    fn flush(&self) {
+        self.first.flush();
+        self.second.flush();
+    }
+
+    fn on_install(&self) {
+        self.first.on_install();
+        self.second.on_install();
+    }
+
+    fn on_remove(&self) {
+        self.first.on_remove();
+        self.second.on_remove();
+    }
+}
+
+/// Delivers to the wrapped sink only the messages accepted by a predicate.
+pub struct Filter {
+    sink : Arc<dyn Sink>,
+    predicate : Box<dyn Fn(&Record<'_>) -> bool + Send + Sync>,
+}
+
+impl Filter {
+    pub fn new<F>(sink : Arc<dyn Sink>, predicate : F) -> Filter
+    where
+        F : Fn(&Record<'_>) -> bool + Send + Sync + 'static,
+    {
+        Filter { sink, predicate : Box::new(predicate) }
+    }
+}
+
+impl Sink for Filter {
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        if (self.predicate)(record) {
+            self.sink.try_write_record(record)
+        } else {
+            Ok(SinkOutput::PassThrough)
+        }
+    }
+
+    fn enabled(&self, level : Level) -> bool {
+        self.sink.enabled(level)
+    }
+
+    fn enabled_for(&self, level : Level, target : &str) -> bool {
+        self.sink.enabled_for(level, target)
+    }
+
+    fn flush(&self) {
+        self.sink.flush();
+    }
+
+    fn on_install(&self) {
+        self.sink.on_install();
+    }
+
+    fn on_remove(&self) {
+        self.sink.on_remove();
+    }
+}
+
+/// Rewrites message text before delivering messages to the wrapped sink.
+/// All other properties of the message are retained.
+pub struct Map {
+    sink : Arc<dyn Sink>,
+    map : Box<dyn Fn(&Record<'_>) -> String + Send + Sync>,
+}
+
+impl Map {
+    pub fn new<F>(sink : Arc<dyn Sink>, map : F) -> Map
+    where
+        F : Fn(&Record<'_>) -> String + Send + Sync + 'static,
+    {
+        Map { sink, map : Box::new(map) }
+    }
+}
+
+impl Sink for Map {
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        let text = (self.map)(record);
+        self.sink.try_write_record(&Record {
+            args : format_args!("{}", text),
+            ..record.clone()
+        })
+    }
+
+    fn enabled(&self, level : Level) -> bool {
+        self.sink.enabled(level)
+    }
+
+    fn enabled_for(&self, level : Level, target : &str) -> bool {
+        self.sink.enabled_for(level, target)
+    }
+
+    fn flush(&self) {
+        self.sink.flush();
+    }
+
+    fn on_install(&self) {
+        self.sink.on_install();
+    }
+
+    fn on_remove(&self) {
+        self.sink.on_remove();
+    }
+}
+
+/// Delivers messages to the primary sink and, if it fails to
+/// process a message (see [`Sink::try_write_record`]), to the
+/// secondary sink.
+pub struct Fallback {
+    primary : Arc<dyn Sink>,
+    secondary : Arc<dyn Sink>,
+}
+
+impl Fallback {
+    pub fn new(primary : Arc<dyn Sink>, secondary : Arc<dyn Sink>) -> Fallback {
+        Fallback { primary, secondary }
+    }
+}
+
+impl Sink for Fallback {
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        match deliver(&self.primary, record) {
+            Err(_) => deliver(&self.secondary, record),
+            result => result
+        }
+    }
+
+    fn enabled(&self, level : Level) -> bool {
+        self.primary.enabled(level) || self.secondary.enabled(level)
+    }
+
+    fn enabled_for(&self, level : Level, target : &str) -> bool {
+        self.primary.enabled_for(level, target) || self.secondary.enabled_for(level, target)
+    }
+
+    fn flush(&self) {
+        self.primary.flush();
+        self.secondary.flush();
+    }
+
+    fn on_install(&self) {
+        self.primary.on_install();
+        self.secondary.on_install();
+    }
+
+    fn on_remove(&self) {
+        self.primary.on_remove();
+        self.secondary.on_remove();
+    }
+}
+
+/// Delivers the record to the sink if the sink is interested in its level and target.
+fn deliver(sink : &Arc<dyn Sink>, record : &Record<'_>) -> io::Result<SinkOutput> {
+    if sink.enabled_for(record.level, record.target) {
+        sink.try_write_record(record)
+    } else {
+        Ok(SinkOutput::PassThrough)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::CollectSink;
+
+    fn write(sink : &dyn Sink, level : Level, text : &str) -> io::Result<SinkOutput> {
+        sink.try_write_record(&Record::new(level, format_args!("{}", text)))
+    }
+
+    #[test]
+    fn tee() {
+        let first = CollectSink::new(false);
+        let second = CollectSink::new(true);
+        let tee = Tee::new(first.clone(), second.clone());
+        assert_eq!(write(&tee, Level::Info, "message").unwrap(), SinkOutput::Consume);
+        assert_eq!(first.texts(), ["message"]);
+        assert_eq!(second.texts(), ["message"]);
+
+        let tee = Tee::new(CollectSink::new(false), CollectSink::new(false));
+        assert_eq!(write(&tee, Level::Info, "message").unwrap(), SinkOutput::PassThrough);
+
+        // sinks not interested in the message are skipped
+        let first = CollectSink::with_level(Some(SinkOutput::Consume), Level::Error);
+        let second = CollectSink::new(false);
+        let tee = Tee::new(first.clone(), second.clone());
+        assert!(tee.enabled(Level::Info));
+        assert_eq!(write(&tee, Level::Info, "message").unwrap(), SinkOutput::PassThrough);
+        assert!(first.texts().is_empty());
+    }
+
+    #[test]
+    fn tee_failures() {
+        // the output of the failed sink is ignored
+        let tee = Tee::new(CollectSink::failing(), CollectSink::new(true));
+        assert_eq!(write(&tee, Level::Info, "message").unwrap(), SinkOutput::Consume);
+        let tee = Tee::new(CollectSink::failing(), CollectSink::failing());
+        assert!(write(&tee, Level::Info, "message").is_err());
+        assert_eq!(tee.write_record(&Record::new(Level::Info, format_args!("message"))), SinkOutput::PassThrough);
+    }
+
+    #[test]
+    fn fallback() {
+        let primary = CollectSink::new(true);
+        let secondary = CollectSink::new(false);
+        let fallback = Fallback::new(primary.clone(), secondary.clone());
+        assert_eq!(write(&fallback, Level::Info, "delivered").unwrap(), SinkOutput::Consume);
+        assert_eq!(primary.texts(), ["delivered"]);
+        assert!(secondary.texts().is_empty());
+
+        let primary = CollectSink::failing();
+        let secondary = CollectSink::new(true);
+        let fallback = Fallback::new(primary.clone(), secondary.clone());
+        assert_eq!(write(&fallback, Level::Info, "failed").unwrap(), SinkOutput::Consume);
+        assert_eq!(primary.texts(), ["failed"]);
+        assert_eq!(secondary.texts(), ["failed"]);
+
+        let fallback = Fallback::new(CollectSink::failing(), CollectSink::failing());
+        assert!(write(&fallback, Level::Info, "failed").is_err());
+
+        // a primary sink not interested in the message does not fail
+        let primary = CollectSink::with_level(None, Level::Error);
+        let secondary = CollectSink::failing();
+        let fallback = Fallback::new(primary, secondary.clone());
+        assert_eq!(write(&fallback, Level::Info, "skipped").unwrap(), SinkOutput::PassThrough);
+        assert!(secondary.texts().is_empty());
+    }
+
+    #[test]
+    fn filter_and_map() {
+        let sink = CollectSink::new(true);
+        let filter = Filter::new(sink.clone(), |record| record.level <= Level::Warn);
+        assert_eq!(write(&filter, Level::Info, "info").unwrap(), SinkOutput::PassThrough);
+        assert_eq!(write(&filter, Level::Warn, "warning").unwrap(), SinkOutput::Consume);
+        assert_eq!(sink.texts(), ["warning"]);
+
+        let sink = CollectSink::new(false);
+        let map = Map::new(sink.clone(), |record| format!("[{}] {}", record.level, record.args));
+        assert!(write(&map, Level::Error, "message").is_ok());
+        let entries = sink.entries();
+        assert_eq!(entries[0].text, "[ERROR] message");
+        assert_eq!(entries[0].level, Level::Error);
+    }
+
+    #[test]
+    fn tee_output() {
+        use SinkOutput::*;
+        // a tee combines the outputs of both sinks
+        let tee = Tee::new(CollectSink::with_output(Replace("a".into())), CollectSink::with_output(Replace("b".into())));
+        assert_eq!(write(&tee, Level::Info, "message").unwrap(), Replace("a".into()));
+        let tee = Tee::new(CollectSink::with_output(Escalate(Level::Warn)), CollectSink::new(true));
+        assert_eq!(write(&tee, Level::Info, "message").unwrap(), Consume);
+    }
+}
@@ -0,0 +1,25 @@
+//!
+//! Helpers shared by the sinks delivering messages to system
+//! and remote log collectors (syslog, journald and GELF).
+//!
+
+use crate::Level;
+
+/// Returns the syslog severity corresponding to the log level.
+pub fn syslog_severity(level : Level) -> u8 {
+    match level {
+        Level::Error => 3,
+        Level::Warn => 4,
+        Level::Info => 6,
+        Level::Debug | Level::Trace => 7,
+    }
+}
+
+/// Returns the host name of the system, or `None` if it can not be determined.
+pub(crate) fn hostname() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/hostname").ok()
+        .map(|hostname| hostname.trim().to_string())
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .filter(|hostname| !hostname.is_empty())
+}
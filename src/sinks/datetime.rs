@@ -0,0 +1,46 @@
+//!
+//! Minimal UTC calendar conversions used by sinks that need to format or
+//! parse dates without pulling in a date/time crate.
+//!
+//! Algorithms from <http://howardhinnant.github.io/date_algorithms.html>
+//!
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Converts days since the unix epoch to a `(year, month, day)` civil date.
+pub fn civil_from_days(days : i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = if z >= 0 { z } else { z - 146096 } / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+/// Converts a `(year, month, day)` civil date to days since the unix epoch.
+pub fn days_from_civil(year : i64, month : u32, day : u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Formats the time as an RFC 3339 UTC timestamp with millisecond
+/// precision, for example `2022-10-25T12:13:20.123Z`.
+pub fn format_timestamp(time : SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year, month, day,
+        secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60,
+        since_epoch.subsec_millis())
+}
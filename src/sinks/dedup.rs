@@ -0,0 +1,279 @@
+use super::clock::Clock;
+use crate::{Level, LogEntry, Record, Sink, SinkOutput};
+use crate::sync::lock;
+use cfg_if::cfg_if;
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// [`Clock`] advancing with the monotonic clock, so that rate limit
+/// intervals are not affected by adjustments of the system time.
+#[cfg(not(target_arch = "wasm32"))]
+struct MonotonicClock {
+    start : std::time::Instant,
+    time : SystemTime,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for MonotonicClock {
+    fn now(&self) -> SystemTime {
+        self.time + self.start.elapsed()
+    }
+}
+
+/// Returns the default clock of [`DedupSink`]. `Instant` is
+/// not available in the browser, so under WASM the system
+/// time is used.
+fn default_clock() -> Arc<dyn Clock> {
+    cfg_if! {
+        if #[cfg(target_arch = "wasm32")] {
+            Arc::new(super::clock::SystemClock)
+        } else {
+            Arc::new(MonotonicClock { start : std::time::Instant::now(), time : SystemTime::now() })
+        }
+    }
+}
+
+struct CallSite {
+    window_start : SystemTime,
+    count : u32,
+    suppressed : u64,
+}
+
+#[derive(Default)]
+struct DedupState {
+    last : Option<LogEntry>,
+    repeats : u64,
+    sites : HashMap<String, CallSite>,
+}
+
+/// A [`Sink`] wrapper that protects the wrapped sink from floods
+/// of log messages.
+///
+/// Consecutive identical messages (same level, target and text) are
+/// collapsed: only the first one is delivered and, once a different
+/// message arrives or the sink is flushed, a single
+/// `last message repeated N times` message is delivered in place of
+/// the repeats.
+///
+/// Optionally, the number of messages delivered from each call site
+/// (the file and line of the log statement) can be limited using
+/// [`DedupSink::with_rate_limit`]. The number of messages suppressed
+/// by the limit is reported when the call site logs again after the
+/// current interval has elapsed.
+///
+/// Suppressed messages are reported as consumed, so they are not
+/// output to the console either.
+pub struct DedupSink {
+    sink : Arc<dyn Sink>,
+    rate_limit : Option<(u32, Duration)>,
+    clock : Arc<dyn Clock>,
+    state : Mutex<DedupState>,
+}
+
+impl DedupSink {
+    pub fn new(sink : Arc<dyn Sink>) -> DedupSink {
+        DedupSink {
+            sink,
+            rate_limit : None,
+            clock : default_clock(),
+            state : Mutex::new(DedupState::default()),
+        }
+    }
+
+    /// Delivers at most `max` messages per `interval` from each call site.
+    pub fn with_rate_limit(mut self, max : u32, interval : Duration) -> Self {
+        self.rate_limit = Some((max, interval));
+        self
+    }
+
+    /// Uses the supplied [`Clock`] to measure rate limit intervals
+    /// instead of the monotonic clock.
+    pub fn with_clock(mut self, clock : Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns `true` if the record exceeds the rate limit of its call site.
+    /// If messages from the call site were suppressed during the previous
+    /// interval, returns the number of suppressed messages.
+    fn rate_limit(&self, state : &mut DedupState, record : &Record<'_>) -> (bool, u64) {
+        let (max, interval) = match self.rate_limit {
+            Some(rate_limit) => rate_limit,
+            None => return (false, 0)
+        };
+        let key = match (record.file, record.line) {
+            (Some(file), Some(line)) => format!("{}:{}", file, line),
+            _ => record.target.to_string()
+        };
+        let now = self.clock.now();
+        let site = state.sites.entry(key).or_insert(CallSite { window_start : now, count : 0, suppressed : 0 });
+        let mut reported = 0;
+        if now.duration_since(site.window_start).unwrap_or_default() >= interval {
+            reported = std::mem::take(&mut site.suppressed);
+            site.window_start = now;
+            site.count = 0;
+        }
+        if site.count >= max {
+            site.suppressed += 1;
+            (true, reported)
+        } else {
+            site.count += 1;
+            (false, reported)
+        }
+    }
+
+    /// Delivers the `last message repeated` summary for pending repeats.
+    fn flush_repeats(&self, state : &mut DedupState) {
+        let repeats = std::mem::take(&mut state.repeats);
+        if repeats > 0 {
+            if let Some(last) = state.last.as_ref() {
+                last.with_record(|record| {
+                    self.sink.write_record(&Record {
+                        args : format_args!("last message repeated {} {}", repeats, if repeats == 1 { "time" } else { "times" }),
+                        ..record.clone()
+                    })
+                });
+            }
+        }
+    }
+}
+
+impl Sink for DedupSink {
+    /// Reports failures of the wrapped sink to process messages that are
+    /// delivered to it; suppressed messages never fail.
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        let mut state = lock(&self.state);
+
+        let (limited, suppressed) = self.rate_limit(&mut state, record);
+        if suppressed > 0 {
+            self.flush_repeats(&mut state);
+            state.last = None;
+            self.sink.write_record(&Record {
+                args : format_args!("{} {} from this call site {} suppressed", suppressed,
+                    if suppressed == 1 { "message" } else { "messages" },
+                    if suppressed == 1 { "was" } else { "were" }),
+                ..record.clone()
+            });
+        }
+        if limited {
+            return Ok(SinkOutput::Consume);
+        }
+
+        let entry = LogEntry::from(record);
+        let repeated = state.last.as_ref().is_some_and(|last| {
+            last.level == entry.level && last.target == entry.target && last.text == entry.text
+        });
+        if repeated {
+            state.repeats += 1;
+            return Ok(SinkOutput::Consume);
+        }
+
+        self.flush_repeats(&mut state);
+        state.last = Some(entry);
+        drop(state);
+        self.sink.try_write_record(record)
+    }
+
+    fn enabled(&self, level : Level) -> bool {
+        self.sink.enabled(level)
+    }
+
+    fn enabled_for(&self, level : Level, target : &str) -> bool {
+        self.sink.enabled_for(level, target)
+    }
+
+    fn flush(&self) {
+        self.flush_repeats(&mut lock(&self.state));
+        self.sink.flush();
+    }
+
+    fn on_install(&self) {
+        self.sink.on_install();
+    }
+
+    fn on_remove(&self) {
+        self.flush_repeats(&mut lock(&self.state));
+        self.sink.on_remove();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::{write, CollectSink, MockClock};
+    use crate::sinks::Fallback;
+
+    #[test]
+    fn failures_reach_fallback() {
+        let secondary = CollectSink::new(false);
+        let sink = Fallback::new(Arc::new(DedupSink::new(CollectSink::failing())), secondary.clone());
+        write(&sink, "one");
+        write(&sink, "one");
+        write(&sink, "two");
+        // the repeat is suppressed by the dedup sink rather than failing
+        assert_eq!(secondary.texts(), ["one", "two"]);
+    }
+
+    #[test]
+    fn repeated_messages() {
+        let collector = CollectSink::new(false);
+        let sink = DedupSink::new(collector.clone());
+        assert_eq!(write(&sink, "one"), SinkOutput::PassThrough);
+        assert_eq!(write(&sink, "one"), SinkOutput::Consume);
+        assert_eq!(write(&sink, "one"), SinkOutput::Consume);
+        assert_eq!(write(&sink, "two"), SinkOutput::PassThrough);
+        // messages of a different level are not repeats
+        sink.write_record(&Record::new(Level::Warn, format_args!("two")));
+        assert_eq!(collector.texts(), ["one", "last message repeated 2 times", "two", "two"]);
+
+        // pending repeats are reported by flush
+        write(&sink, "three");
+        write(&sink, "three");
+        sink.flush();
+        sink.flush();
+        assert_eq!(collector.texts()[4..], ["three", "last message repeated 1 time"]);
+        let entries = collector.entries();
+        assert_eq!(entries[1].level, Level::Info);
+        assert_eq!(entries[3].level, Level::Warn);
+    }
+
+    fn write_at(sink : &dyn Sink, line : u32, text : &str) -> SinkOutput {
+        sink.write_record(&Record {
+            file : Some("src/main.rs"),
+            line : Some(line),
+            ..Record::new(Level::Info, format_args!("{}", text))
+        })
+    }
+
+    #[test]
+    fn rate_limit() {
+        let collector = CollectSink::new(false);
+        let clock = MockClock::new(0);
+        let sink = DedupSink::new(collector.clone())
+            .with_rate_limit(2, Duration::from_millis(200))
+            .with_clock(clock.clone());
+        for index in 0..5 {
+            assert_eq!(write_at(&sink, 10, &format!("request {}", index)), (index >= 2).into());
+        }
+        // call sites are limited independently
+        assert_eq!(write_at(&sink, 20, "other"), SinkOutput::PassThrough);
+        assert_eq!(collector.texts(), ["request 0", "request 1", "other"]);
+
+        clock.advance(Duration::from_millis(200));
+        assert_eq!(write_at(&sink, 10, "request 5"), SinkOutput::PassThrough);
+        assert_eq!(collector.texts()[3..], ["3 messages from this call site were suppressed", "request 5"]);
+
+        // nothing is reported if no messages were suppressed
+        clock.advance(Duration::from_millis(200));
+        write_at(&sink, 10, "request 6");
+        assert_eq!(collector.texts()[5..], ["request 6"]);
+
+        write_at(&sink, 10, "request 7");
+        write_at(&sink, 10, "request 8");
+        clock.advance(Duration::from_millis(200));
+        write_at(&sink, 10, "request 9");
+        assert_eq!(collector.texts()[6..], ["request 7", "1 message from this call site was suppressed", "request 9"]);
+    }
+}
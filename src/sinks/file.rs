@@ -0,0 +1,283 @@
+use super::datetime::format_timestamp;
+use crate::{Record, Sink, SinkOutput};
+use crate::sync::lock;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+struct FileState {
+    file : Option<File>,
+    size : u64,
+}
+
+/// A [`Sink`] that appends log messages to a file, rotating the file
+/// once it reaches a configurable size.
+///
+/// When rotated, `app.log` is renamed to `app.log.1`, `app.log.1` to
+/// `app.log.2` and so on, retaining at most `max_backups` files. If
+/// compression is enabled (requires the `gzip` feature), rotated files
+/// are gzipped and named `app.log.1.gz`, `app.log.2.gz` etc.
+///
+/// Writes and rotation are serialized internally, so the sink can be
+/// used while multiple threads are logging. Rotation (and compression)
+/// is performed on the logging thread; wrap the sink in an
+/// [`AsyncSink`](super::AsyncSink) to move this work off the logging threads.
+pub struct FileSink {
+    path : PathBuf,
+    max_size : u64,
+    max_backups : usize,
+    compress : bool,
+    state : Mutex<FileState>,
+}
+
+impl FileSink {
+    /// Opens (or creates) the log file at `path` for appending.
+    /// By default the file is rotated at 10 MiB and 5 backups are retained.
+    pub fn new<P : AsRef<Path>>(path : P) -> io::Result<FileSink> {
+        let path = path.as_ref().to_path_buf();
+        let file = open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(FileSink {
+            path,
+            max_size : 10 * 1024 * 1024,
+            max_backups : 5,
+            compress : false,
+            state : Mutex::new(FileState { file : Some(file), size }),
+        })
+    }
+
+    /// Sets the size in bytes at which the log file is rotated.
+    pub fn with_max_size(mut self, max_size : u64) -> Self {
+        self.max_size = max_size.max(1);
+        self
+    }
+
+    /// Sets the number of rotated files to retain. If set to `0`,
+    /// the log file is truncated when rotated.
+    pub fn with_max_backups(mut self, max_backups : usize) -> Self {
+        self.max_backups = max_backups;
+        self
+    }
+
+    /// Enables gzip compression of rotated files.
+    #[cfg(feature = "gzip")]
+    pub fn with_compression(mut self, compress : bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Returns the path of the current log file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Rotates the log file regardless of its size.
+    pub fn rotate(&self) -> io::Result<()> {
+        let mut state = lock(&self.state);
+        self.rotate_impl(&mut state)
+    }
+
+    fn backup_path(&self, index : usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", index));
+        if self.compress {
+            path.push(".gz");
+        }
+        PathBuf::from(path)
+    }
+
+    fn rotate_impl(&self, state : &mut FileState) -> io::Result<()> {
+        // close the current file before renaming it
+        state.file.take();
+        state.size = 0;
+
+        if self.max_backups > 0 {
+            let oldest = self.backup_path(self.max_backups);
+            if oldest.exists() {
+                fs::remove_file(&oldest)?;
+            }
+            for index in (1..self.max_backups).rev() {
+                let from = self.backup_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.backup_path(index + 1))?;
+                }
+            }
+            if !self.path.exists() {
+                // nothing to back up
+            } else if self.compress {
+                compress(&self.path, &self.backup_path(1))?;
+                fs::remove_file(&self.path)?;
+            } else {
+                fs::rename(&self.path, self.backup_path(1))?;
+            }
+        } else if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+
+        state.file = Some(open(&self.path)?);
+        Ok(())
+    }
+
+    fn write_impl(&self, state : &mut FileState, line : &str) -> io::Result<()> {
+        let len = line.len() as u64;
+        if state.size > 0 && state.size + len > self.max_size {
+            self.rotate_impl(state)?;
+        }
+        if state.file.is_none() {
+            state.file = Some(open(&self.path)?);
+        }
+        if let Some(file) = state.file.as_mut() {
+            file.write_all(line.as_bytes())?;
+            state.size += len;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for FileSink {
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        let line = format_line(record);
+        let mut state = lock(&self.state);
+        self.write_impl(&mut state, &line)?;
+        Ok(SinkOutput::PassThrough)
+    }
+
+    /// Closes the log file. It is reopened if the sink receives further messages.
+    fn on_remove(&self) {
+        lock(&self.state).file.take();
+    }
+}
+
+/// Formats a record as a line of text, for example
+/// `2022-10-25T12:13:20.123Z [INFO] my_crate::module: message`
+pub(super) fn format_line(record : &Record<'_>) -> String {
+    let timestamp = format_timestamp(record.timestamp);
+    if record.target.is_empty() {
+        format!("{} [{}] {}\n", timestamp, record.level, record.args)
+    } else {
+        format!("{} [{}] {}: {}\n", timestamp, record.level, record.target, record.args)
+    }
+}
+
+pub(super) fn open(path : &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(feature = "gzip")]
+fn compress(from : &Path, to : &Path) -> io::Result<()> {
+    use flate2::{write::GzEncoder, Compression};
+    let mut input = File::open(from)?;
+    let mut encoder = GzEncoder::new(File::create(to)?, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "gzip"))]
+fn compress(_from : &Path, _to : &Path) -> io::Result<()> {
+    unreachable!("compression requires the `gzip` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::{directory, record};
+
+    fn log(sink : &FileSink, text : &str) {
+        assert!(sink.try_write_record(&record(format_args!("{}", text))).is_ok());
+    }
+
+    /// Returns the messages contained in the file.
+    fn read(path : &Path) -> Vec<String> {
+        lines(&fs::read_to_string(path).unwrap())
+    }
+
+    fn lines(text : &str) -> Vec<String> {
+        text.lines().map(|line| line.strip_prefix("2023-11-04T09:08:07.250Z [INFO] ").unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn rotation() {
+        let directory = directory("file-rotation");
+        let path = directory.join("app.log");
+        // each line is 35 bytes long, so that files hold up to two lines
+        let sink = FileSink::new(&path).unwrap().with_max_size(70).with_max_backups(2);
+        for index in 1..=7 {
+            log(&sink, &format!("m{}", index));
+        }
+        assert_eq!(read(&path), ["m7"]);
+        assert_eq!(read(&directory.join("app.log.1")), ["m5", "m6"]);
+        assert_eq!(read(&directory.join("app.log.2")), ["m3", "m4"]);
+        assert!(!directory.join("app.log.3").exists());
+
+        sink.rotate().unwrap();
+        assert_eq!(read(&path), Vec::<String>::new());
+        assert_eq!(read(&directory.join("app.log.1")), ["m7"]);
+        assert_eq!(read(&directory.join("app.log.2")), ["m5", "m6"]);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn existing_file() {
+        let directory = directory("file-existing");
+        let path = directory.join("app.log");
+        fs::write(&path, format_line(&record(format_args!("m0")))).unwrap();
+        // the size of the existing file counts towards the maximum size
+        let sink = FileSink::new(&path).unwrap().with_max_size(70);
+        log(&sink, "m1");
+        log(&sink, "m2");
+        assert_eq!(read(&path), ["m2"]);
+        assert_eq!(read(&directory.join("app.log.1")), ["m0", "m1"]);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn no_backups() {
+        let directory = directory("file-no-backups");
+        let path = directory.join("app.log");
+        let sink = FileSink::new(&path).unwrap().with_max_size(70).with_max_backups(0);
+        for index in 1..=3 {
+            log(&sink, &format!("m{}", index));
+        }
+        assert_eq!(read(&path), ["m3"]);
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn reopen_after_removal() {
+        let directory = directory("file-reopen");
+        let path = directory.join("app.log");
+        let sink = FileSink::new(&path).unwrap();
+        log(&sink, "m1");
+        sink.on_remove();
+        log(&sink, "m2");
+        assert_eq!(read(&path), ["m1", "m2"]);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn compression() {
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let directory = directory("file-compression");
+        let path = directory.join("app.log");
+        let sink = FileSink::new(&path).unwrap().with_max_size(70).with_max_backups(2).with_compression(true);
+        for index in 1..=5 {
+            log(&sink, &format!("m{}", index));
+        }
+        let decompress = |name : &str| {
+            let mut text = String::new();
+            GzDecoder::new(File::open(directory.join(name)).unwrap()).read_to_string(&mut text).unwrap();
+            lines(&text)
+        };
+        assert_eq!(read(&path), ["m5"]);
+        assert_eq!(decompress("app.log.1.gz"), ["m3", "m4"]);
+        assert_eq!(decompress("app.log.2.gz"), ["m1", "m2"]);
+        assert!(!directory.join("app.log.1").exists());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,402 @@
+use super::common::{hostname, syslog_severity};
+use super::json::write_string;
+use super::network::{Connection, Transport};
+use crate::sync::lock;
+use crate::{Record, Sink, SinkOutput};
+use std::fmt::Write;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+/// Magic bytes starting each chunk of a chunked GELF message.
+const CHUNK_MAGIC : [u8; 2] = [0x1e, 0x0f];
+/// Size of the chunk header: magic bytes, message id, sequence number and count.
+const CHUNK_HEADER_SIZE : usize = 12;
+/// Maximum number of chunks of a message.
+const MAX_CHUNKS : usize = 128;
+
+struct GelfState {
+    connection : Option<Connection>,
+    /// Earliest time of the next connection attempt after a failure
+    retry_at : Option<Instant>,
+    backoff : Duration,
+}
+
+/// A [`Sink`] sending log messages to Graylog (or another GELF-compatible
+/// receiver) as [GELF 1.1](https://go2docs.graylog.org/current/getting_in_log_data/gelf.html)
+/// messages over UDP or TCP.
+///
+/// The first line of the message is sent as `short_message`, while multi-line
+/// messages are also sent in full as `full_message`. The message level is
+/// mapped to the syslog severity using [`syslog_severity`], and the record target, location, structured
+/// fields and any additional fields configured using [`GelfSink::with_field`]
+/// are sent as `_`-prefixed additional fields.
+///
+/// Over UDP, messages exceeding the chunk size are split into GELF chunks and
+/// can be gzip-compressed (requires the `gzip` feature). Over TCP, messages are
+/// null-byte delimited and the connection is re-established as needed, with
+/// connection attempts following a failure delayed using exponential backoff.
+///
+/// Messages are sent on the logging thread. UDP datagrams are sent without
+/// waiting for the receiver, while over TCP a connection attempt can block
+/// the logging thread for up to 5 seconds and a write for up to 10 seconds
+/// if Graylog stops reading. After a failed connection attempt, messages
+/// fail immediately until the backoff delay has elapsed. Failures are
+/// reported by [`Sink::try_write_record`], so that a [`Fallback`](super::Fallback)
+/// sink can receive the messages Graylog did not.
+pub struct GelfSink {
+    addr : String,
+    transport : Transport,
+    host : String,
+    chunk_size : usize,
+    compress : bool,
+    fields : Vec<(String, String)>,
+    state : Mutex<GelfState>,
+    message_id : AtomicU64,
+}
+
+impl GelfSink {
+    /// Creates a sink sending messages to `addr` (e.g. `"graylog:12201"`).
+    /// The `host` field defaults to the host name of the system and UDP
+    /// messages are split into chunks of up to 1420 bytes.
+    pub fn new(addr : &str, transport : Transport) -> GelfSink {
+        let seed = std::time::SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+        GelfSink {
+            addr : addr.to_string(),
+            transport,
+            host : hostname().unwrap_or_else(|| "localhost".to_string()),
+            chunk_size : 1420,
+            compress : false,
+            fields : Vec::new(),
+            state : Mutex::new(GelfState {
+                connection : None,
+                retry_at : None,
+                backoff : Duration::from_millis(100),
+            }),
+            message_id : AtomicU64::new(seed ^ ((std::process::id() as u64) << 32)),
+        }
+    }
+
+    /// Sets the `host` field of the messages.
+    pub fn with_host(mut self, host : &str) -> Self {
+        self.host = host.to_string();
+        self
+    }
+
+    /// Adds an additional field sent with every message,
+    /// e.g. `with_field("environment", "production")`.
+    pub fn with_field(mut self, key : &str, value : &str) -> Self {
+        self.fields.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the maximum size of UDP datagrams, including
+    /// the chunk header (at least 512 bytes).
+    pub fn with_chunk_size(mut self, chunk_size : usize) -> Self {
+        self.chunk_size = chunk_size.max(512);
+        self
+    }
+
+    /// Enables gzip compression of UDP messages.
+    #[cfg(feature = "gzip")]
+    pub fn with_compression(mut self, compress : bool) -> Self {
+        self.compress = compress;
+        self
+    }
+
+    /// Serializes the record as a GELF message.
+    pub fn format_message(&self, record : &Record<'_>) -> String {
+        let message = record.args.to_string();
+        let mut lines = message.lines();
+        let short_message = lines.next().filter(|line| !line.trim().is_empty()).unwrap_or("-");
+        let since_epoch = record.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+        let mut out = String::with_capacity(256);
+        out.push_str("{\"version\":\"1.1\",\"host\":");
+        write_string(&mut out, &self.host);
+        out.push_str(",\"short_message\":");
+        write_string(&mut out, short_message);
+        if lines.next().is_some() {
+            out.push_str(",\"full_message\":");
+            write_string(&mut out, &message);
+        }
+        let _ = write!(out, ",\"timestamp\":{}.{:03},\"level\":{}",
+            since_epoch.as_secs(), since_epoch.subsec_millis(), syslog_severity(record.level));
+        if !record.target.is_empty() {
+            write_field(&mut out, "target", record.target);
+        }
+        if let Some(module_path) = record.module_path {
+            write_field(&mut out, "module_path", module_path);
+        }
+        if let Some(file) = record.file {
+            write_field(&mut out, "file", file);
+        }
+        if let Some(line) = record.line {
+            let _ = write!(out, ",\"_line\":{}", line);
+        }
+        for (key, value) in self.fields.iter() {
+            write_field(&mut out, key, value);
+        }
+        for field in record.fields.iter() {
+            write_field(&mut out, field.key, &field.value.to_string());
+        }
+        out.push('}');
+        out
+    }
+
+    /// Opens a connection, unless a previous attempt has failed
+    /// and the backoff delay has not elapsed yet.
+    fn connect(&self, state : &mut GelfState) -> io::Result<Connection> {
+        if state.retry_at.is_some_and(|retry_at| Instant::now() < retry_at) {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "waiting to reconnect"));
+        }
+        Connection::open(&self.addr, self.transport).inspect_err(|_| {
+            state.retry_at = Some(Instant::now() + state.backoff);
+            state.backoff = (state.backoff * 2).min(Duration::from_secs(30));
+        })
+    }
+
+    fn send(&self, state : &mut GelfState, message : &[u8]) -> io::Result<()> {
+        let mut connection = match state.connection.take() {
+            Some(connection) => connection,
+            None => self.connect(state)?
+        };
+        let result = self.send_message(&mut connection, message);
+        // the connection is reopened for the next message if sending has failed
+        if result.is_ok() {
+            state.connection = Some(connection);
+            state.retry_at = None;
+            state.backoff = Duration::from_millis(100);
+        }
+        result
+    }
+
+    fn send_message(&self, connection : &mut Connection, message : &[u8]) -> io::Result<()> {
+        match self.transport {
+            Transport::Tcp => {
+                let mut frame = Vec::with_capacity(message.len() + 1);
+                frame.extend_from_slice(message);
+                frame.push(0);
+                connection.send(&frame)
+            },
+            Transport::Udp => {
+                if message.len() <= self.chunk_size {
+                    connection.send(message)
+                } else {
+                    let chunk_size = self.chunk_size - CHUNK_HEADER_SIZE;
+                    let count = message.len().div_ceil(chunk_size);
+                    if count > MAX_CHUNKS {
+                        return Err(io::Error::new(io::ErrorKind::InvalidInput, "message exceeds the maximum number of GELF chunks"));
+                    }
+                    let id = self.next_message_id();
+                    message.chunks(chunk_size).enumerate().try_for_each(|(sequence, payload)| {
+                        let mut chunk = Vec::with_capacity(CHUNK_HEADER_SIZE + payload.len());
+                        chunk.extend_from_slice(&CHUNK_MAGIC);
+                        chunk.extend_from_slice(&id.to_be_bytes());
+                        chunk.push(sequence as u8);
+                        chunk.push(count as u8);
+                        chunk.extend_from_slice(payload);
+                        connection.send(&chunk)
+                    })
+                }
+            }
+        }
+    }
+
+    /// Returns the id of a chunked message. Ids are consecutive, starting
+    /// from a value derived from the time and the process id, so that the
+    /// ids of messages sent by different processes are unlikely to collide.
+    fn next_message_id(&self) -> u64 {
+        self.message_id.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+impl Sink for GelfSink {
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        let message = self.format_message(record);
+        let message = if self.compress && self.transport == Transport::Udp {
+            compress(message.as_bytes())?
+        } else {
+            message.into_bytes()
+        };
+        self.send(&mut lock(&self.state), &message)?;
+        Ok(SinkOutput::PassThrough)
+    }
+
+    /// Closes the TCP connection. It is reopened if the sink receives further messages.
+    fn on_remove(&self) {
+        lock(&self.state).connection.take();
+    }
+}
+
+/// Appends an additional field. Characters not allowed in GELF
+/// field names are replaced with `_`; `id` is sent as `_id_`.
+fn write_field(out : &mut String, key : &str, value : &str) {
+    let mut name = String::with_capacity(key.len() + 2);
+    name.push('_');
+    name.extend(key.chars().map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' { c } else { '_' }));
+    if name == "_id" {
+        name.push('_');
+    }
+    out.push(',');
+    write_string(out, &name);
+    out.push(':');
+    write_string(out, value);
+}
+
+#[cfg(feature = "gzip")]
+fn compress(message : &[u8]) -> io::Result<Vec<u8>> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(message)?;
+    encoder.finish()
+}
+
+#[cfg(not(feature = "gzip"))]
+fn compress(_message : &[u8]) -> io::Result<Vec<u8>> {
+    unreachable!("compression requires the `gzip` feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::testing;
+    use crate::{Field, Level};
+    use std::io::Read;
+    use std::net::{TcpListener, UdpSocket};
+
+    fn record(args : std::fmt::Arguments<'_>) -> Record<'_> {
+        Record { level : Level::Warn, ..testing::record(args) }
+    }
+
+    fn udp_receiver() -> (UdpSocket, String) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        let addr = socket.local_addr().unwrap().to_string();
+        (socket, addr)
+    }
+
+    fn receive(socket : &UdpSocket) -> Vec<u8> {
+        let mut buffer = vec![0u8; 65536];
+        let len = socket.recv(&mut buffer).unwrap();
+        buffer.truncate(len);
+        buffer
+    }
+
+    #[test]
+    fn format() {
+        let sink = GelfSink::new("127.0.0.1:12201", Transport::Udp)
+            .with_host("host")
+            .with_field("environment", "test");
+        let peer = "10.0.0.1";
+        let id = 7;
+        let fields = [Field::new("peer addr", &peer), Field::new("id", &id)];
+        let message = sink.format_message(&Record {
+            target : "server",
+            line : Some(42),
+            fields : &fields,
+            ..record(format_args!("connection lost\n\"retrying\""))
+        });
+        assert_eq!(message, "{\"version\":\"1.1\",\"host\":\"host\",\"short_message\":\"connection lost\",\
+            \"full_message\":\"connection lost\\n\\\"retrying\\\"\",\"timestamp\":1699088887.250,\"level\":4,\
+            \"_target\":\"server\",\"_line\":42,\"_environment\":\"test\",\"_peer_addr\":\"10.0.0.1\",\"_id_\":\"7\"}");
+
+        let message = sink.format_message(&Record { level : Level::Debug, ..record(format_args!("")) });
+        assert!(message.contains("\"short_message\":\"-\",\"timestamp\":1699088887.250,\"level\":7,\"_environment\""), "{}", message);
+    }
+
+    #[test]
+    fn udp() {
+        let (socket, addr) = udp_receiver();
+        let sink = GelfSink::new(&addr, Transport::Udp).with_host("host");
+        let record = record(format_args!("message"));
+        assert!(sink.try_write_record(&record).is_ok());
+        assert_eq!(receive(&socket), sink.format_message(&record).into_bytes());
+    }
+
+    #[test]
+    fn udp_chunks() {
+        let (socket, addr) = udp_receiver();
+        let sink = GelfSink::new(&addr, Transport::Udp).with_chunk_size(512);
+        let text = "x".repeat(1200);
+        let message = sink.format_message(&record(format_args!("{}", text))).into_bytes();
+        let count = message.len().div_ceil(500);
+        assert_eq!(count, 3);
+
+        for _ in 0..2 {
+            assert!(sink.try_write_record(&record(format_args!("{}", text))).is_ok());
+            let chunks = (0..count).map(|_| receive(&socket)).collect::<Vec<_>>();
+            let mut payload = Vec::new();
+            for (sequence, chunk) in chunks.iter().enumerate() {
+                assert!(chunk.len() <= 512);
+                assert_eq!(chunk[..2], CHUNK_MAGIC);
+                assert_eq!(chunk[2..10], chunks[0][2..10]);
+                assert_eq!(chunk[10], sequence as u8);
+                assert_eq!(chunk[11], count as u8);
+                payload.extend_from_slice(&chunk[CHUNK_HEADER_SIZE..]);
+            }
+            assert_eq!(payload, message);
+        }
+    }
+
+    #[test]
+    fn udp_message_ids() {
+        let sink = GelfSink::new("127.0.0.1:12201", Transport::Udp);
+        let ids = (0..100).map(|_| sink.next_message_id()).collect::<std::collections::HashSet<_>>();
+        assert_eq!(ids.len(), 100);
+    }
+
+    #[test]
+    fn udp_chunk_limit() {
+        let (_socket, addr) = udp_receiver();
+        let sink = GelfSink::new(&addr, Transport::Udp).with_chunk_size(512);
+        let text = "x".repeat(MAX_CHUNKS * 500);
+        let err = sink.try_write_record(&record(format_args!("{}", text))).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn udp_compression() {
+        use flate2::read::GzDecoder;
+        let (socket, addr) = udp_receiver();
+        let sink = GelfSink::new(&addr, Transport::Udp).with_compression(true);
+        let record = record(format_args!("message"));
+        assert!(sink.try_write_record(&record).is_ok());
+        let mut message = String::new();
+        GzDecoder::new(receive(&socket).as_slice()).read_to_string(&mut message).unwrap();
+        assert_eq!(message, sink.format_message(&record));
+    }
+
+    #[test]
+    fn tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let sink = GelfSink::new(&listener.local_addr().unwrap().to_string(), Transport::Tcp);
+        let first = record(format_args!("first"));
+        let second = record(format_args!("second"));
+        assert!(sink.try_write_record(&first).is_ok());
+        assert!(sink.try_write_record(&second).is_ok());
+        sink.on_remove();
+
+        let mut received = Vec::new();
+        listener.accept().unwrap().0.read_to_end(&mut received).unwrap();
+        let mut expected = sink.format_message(&first).into_bytes();
+        expected.push(0);
+        expected.extend_from_slice(sink.format_message(&second).as_bytes());
+        expected.push(0);
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn tcp_backoff() {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let sink = GelfSink::new(&addr, Transport::Tcp);
+        let record = record(format_args!("message"));
+        assert_eq!(sink.try_write_record(&record).unwrap_err().kind(), io::ErrorKind::ConnectionRefused);
+        // further attempts are delayed
+        assert_eq!(sink.try_write_record(&record).unwrap_err().kind(), io::ErrorKind::NotConnected);
+        assert_eq!(lock(&sink.state).backoff, Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,195 @@
+use super::common::syslog_severity;
+use crate::{Record, Sink, SinkOutput};
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+
+/// A [`Sink`] that sends log messages to systemd-journald using its
+/// [native protocol](https://systemd.io/JOURNAL_NATIVE_PROTOCOL/) over
+/// the journal socket (`/run/systemd/journal/socket` by default).
+///
+/// Each message is sent as a single datagram containing the `MESSAGE`,
+/// `PRIORITY` (see [`syslog_severity`](super::syslog_severity)),
+/// `SYSLOG_IDENTIFIER`, `TARGET`, `CODE_FILE`, `CODE_LINE` and
+/// `CODE_MODULE` fields, followed by the structured fields of the record.
+/// Structured field names are converted to valid journal field names,
+/// e.g. `peer_addr` is sent as `PEER_ADDR`. Fields that would collide with
+/// the fields above or other fields interpreted by the journal (see
+/// [`systemd.journal-fields`](https://www.freedesktop.org/software/systemd/man/systemd.journal-fields.html))
+/// are prefixed with `F_`, e.g. `message` is sent as `F_MESSAGE`.
+///
+/// Messages exceeding the maximum datagram size of the socket are
+/// reported as errors by [`Sink::try_write_record`].
+pub struct JournaldSink {
+    socket : UnixDatagram,
+    path : PathBuf,
+    identifier : Option<String>,
+}
+
+impl JournaldSink {
+    /// Creates a sink sending messages to the journal socket.
+    /// The syslog identifier defaults to the name of the current executable.
+    pub fn new() -> io::Result<JournaldSink> {
+        let identifier = std::env::current_exe().ok()
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()));
+        Ok(JournaldSink {
+            socket : UnixDatagram::unbound()?,
+            path : PathBuf::from("/run/systemd/journal/socket"),
+            identifier,
+        })
+    }
+
+    /// Sets the path of the journal socket.
+    pub fn with_path<P : AsRef<Path>>(mut self, path : P) -> Self {
+        self.path = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Sets the `SYSLOG_IDENTIFIER` field of the messages.
+    pub fn with_identifier(mut self, identifier : &str) -> Self {
+        self.identifier = Some(identifier.to_string());
+        self
+    }
+
+    /// Returns the path of the journal socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Serializes the record using the journal native protocol.
+    pub fn format_message(&self, record : &Record<'_>) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        append_field(&mut buffer, "MESSAGE", &record.args.to_string());
+        append_field(&mut buffer, "PRIORITY", &syslog_severity(record.level).to_string());
+        if let Some(identifier) = self.identifier.as_deref() {
+            append_field(&mut buffer, "SYSLOG_IDENTIFIER", identifier);
+        }
+        if !record.target.is_empty() {
+            append_field(&mut buffer, "TARGET", record.target);
+        }
+        if let Some(file) = record.file {
+            append_field(&mut buffer, "CODE_FILE", file);
+        }
+        if let Some(line) = record.line {
+            append_field(&mut buffer, "CODE_LINE", &line.to_string());
+        }
+        if let Some(module_path) = record.module_path {
+            append_field(&mut buffer, "CODE_MODULE", module_path);
+        }
+        for field in record.fields.iter() {
+            append_field(&mut buffer, &field_name(field.key), &field.value.to_string());
+        }
+        buffer
+    }
+}
+
+impl Sink for JournaldSink {
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        let message = self.format_message(record);
+        self.socket.send_to(&message, &self.path)?;
+        Ok(SinkOutput::PassThrough)
+    }
+}
+
+/// Appends a field to the message. Values containing newlines are
+/// serialized in the binary form, prefixed with their length.
+fn append_field(buffer : &mut Vec<u8>, name : &str, value : &str) {
+    buffer.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        buffer.push(b'\n');
+        buffer.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        buffer.push(b'=');
+    }
+    buffer.extend_from_slice(value.as_bytes());
+    buffer.push(b'\n');
+}
+
+/// Fields set by [`JournaldSink`] or interpreted by the journal,
+/// which structured fields must not override.
+const RESERVED_FIELDS : &[&str] = &[
+    "MESSAGE", "MESSAGE_ID", "PRIORITY", "TARGET", "CODE_FILE", "CODE_LINE",
+    "CODE_FUNC", "CODE_MODULE", "ERRNO", "INVOCATION_ID", "USER_INVOCATION_ID",
+    "SYSLOG_FACILITY", "SYSLOG_IDENTIFIER", "SYSLOG_PID", "SYSLOG_TIMESTAMP",
+    "SYSLOG_RAW", "DOCUMENTATION", "TID", "UNIT", "USER_UNIT",
+];
+
+/// Converts a field key to a journal field name, which may only contain
+/// uppercase letters, digits and underscores and must start with a letter
+/// (names starting with an underscore are reserved for trusted fields).
+/// Names of [reserved fields](RESERVED_FIELDS) are prefixed with `F_`.
+fn field_name(key : &str) -> String {
+    let name = key.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .take(64)
+        .collect::<String>();
+    if RESERVED_FIELDS.contains(&name.as_str()) {
+        format!("F_{}", name)
+    } else if name.starts_with(|c : char| c.is_ascii_uppercase()) {
+        name
+    } else {
+        format!("F{}", name).chars().take(64).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Field, Level};
+    use crate::sinks::testing::directory;
+
+    #[test]
+    fn datagram() {
+        let path = directory("journald").join("socket");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        let sink = JournaldSink::new().unwrap().with_path(&path).with_identifier("app");
+
+        let peer = "10.0.0.1";
+        let message = "first line";
+        let fields = [Field::new("peer.addr", &peer), Field::new("message", &message)];
+        let record = Record {
+            target : "server",
+            file : Some("src/main.rs"),
+            line : Some(42),
+            module_path : Some("app::server"),
+            fields : &fields,
+            ..Record::new(Level::Warn, format_args!("connection lost\nretrying"))
+        };
+        assert!(sink.try_write_record(&record).is_ok());
+
+        let mut buffer = [0u8; 1024];
+        let len = socket.recv(&mut buffer).unwrap();
+
+        let mut expected = b"MESSAGE\n".to_vec();
+        expected.extend_from_slice(&24u64.to_le_bytes());
+        expected.extend_from_slice(b"connection lost\nretrying\n\
+            PRIORITY=4\n\
+            SYSLOG_IDENTIFIER=app\n\
+            TARGET=server\n\
+            CODE_FILE=src/main.rs\n\
+            CODE_LINE=42\n\
+            CODE_MODULE=app::server\n\
+            PEER_ADDR=10.0.0.1\n\
+            F_MESSAGE=first line\n");
+        assert_eq!(&buffer[..len], &expected[..]);
+    }
+
+    #[test]
+    fn missing_socket() {
+        let path = directory("journald-missing").join("socket");
+        let sink = JournaldSink::new().unwrap().with_path(path);
+        assert!(sink.try_write_record(&Record::new(Level::Info, format_args!("message"))).is_err());
+    }
+
+    #[test]
+    fn field_names() {
+        assert_eq!(field_name("peer_addr"), "PEER_ADDR");
+        assert_eq!(field_name("peer.addr"), "PEER_ADDR");
+        assert_eq!(field_name("_trusted"), "F_TRUSTED");
+        assert_eq!(field_name("1st"), "F1ST");
+        assert_eq!(field_name("message"), "F_MESSAGE");
+        assert_eq!(field_name("Priority"), "F_PRIORITY");
+        assert_eq!(field_name("code_file"), "F_CODE_FILE");
+        assert_eq!(field_name("syslog_identifier"), "F_SYSLOG_IDENTIFIER");
+    }
+}
@@ -0,0 +1,68 @@
+//!
+//! Minimal JSON serialization used by the network sinks.
+//!
+
+use super::datetime::format_timestamp;
+use crate::Record;
+use std::fmt::Write;
+
+/// Appends `value` to `out` as a quoted JSON string.
+pub fn write_string(out : &mut String, value : &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); },
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Serializes the record as a single-line JSON object:
+///
+/// `{"timestamp":"2022-10-25T12:13:20.123Z","level":"INFO","target":"net",
+/// "module_path":"app::net","file":"src/net.rs","line":42,"message":"...",
+/// "fields":{"peer":"..."}}`
+///
+/// `module_path`, `file`, `line` and `fields` are omitted if not available.
+pub fn format_record(record : &Record<'_>) -> String {
+    let mut out = String::with_capacity(128);
+    out.push_str("{\"timestamp\":");
+    write_string(&mut out, &format_timestamp(record.timestamp));
+    out.push_str(",\"level\":");
+    write_string(&mut out, record.level.as_str());
+    out.push_str(",\"target\":");
+    write_string(&mut out, record.target);
+    if let Some(module_path) = record.module_path {
+        out.push_str(",\"module_path\":");
+        write_string(&mut out, module_path);
+    }
+    if let Some(file) = record.file {
+        out.push_str(",\"file\":");
+        write_string(&mut out, file);
+    }
+    if let Some(line) = record.line {
+        let _ = write!(out, ",\"line\":{}", line);
+    }
+    out.push_str(",\"message\":");
+    write_string(&mut out, &record.args.to_string());
+    if !record.fields.is_empty() {
+        out.push_str(",\"fields\":{");
+        for (index, field) in record.fields.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            write_string(&mut out, field.key);
+            out.push(':');
+            write_string(&mut out, &field.value.to_string());
+        }
+        out.push('}');
+    }
+    out.push('}');
+    out
+}
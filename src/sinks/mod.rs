@@ -0,0 +1,51 @@
+//!
+//! Built-in [`Sink`](crate::Sink) implementations that can be registered
+//! using [`add_sink`](crate::add_sink) or [`pipe`](crate::pipe).
+//!
+
+use cfg_if::cfg_if;
+
+mod clock;
+pub use clock::*;
+mod combinators;
+pub use combinators::*;
+mod dedup;
+pub use dedup::*;
+mod ring_buffer;
+pub use ring_buffer::*;
+
+#[cfg(test)]
+pub(crate) mod testing;
+
+cfg_if! {
+    if #[cfg(not(target_arch = "wasm32"))] {
+        mod async_sink;
+        pub use async_sink::*;
+        mod common;
+        pub use common::*;
+        mod datetime;
+        mod file;
+        pub use file::*;
+        mod gelf;
+        pub use gelf::*;
+        mod json;
+        mod network;
+        pub use network::*;
+        #[cfg(feature = "otlp")]
+        mod otlp;
+        #[cfg(feature = "otlp")]
+        pub use otlp::*;
+        mod receiver;
+        pub use receiver::*;
+        mod rolling;
+        pub use rolling::*;
+        #[cfg(unix)]
+        mod journald;
+        #[cfg(unix)]
+        pub use journald::*;
+        #[cfg(unix)]
+        mod syslog;
+        #[cfg(unix)]
+        pub use syslog::*;
+    }
+}
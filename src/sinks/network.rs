@@ -0,0 +1,542 @@
+use super::json::format_record;
+use crate::sync::{lock, wait, wait_timeout};
+use crate::{Record, Sink, SinkOutput};
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const CONNECT_TIMEOUT : Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT : Duration = Duration::from_secs(10);
+
+/// Network protocol used by [`NetworkSink`], [`LogReceiver`](super::LogReceiver)
+/// and [`GelfSink`](super::GelfSink).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Tcp,
+    /// Each record is sent as a separate datagram.
+    Udp,
+}
+
+/// Framing of the JSON-serialized records sent by [`NetworkSink`].
+/// See [`NetworkSink`] for the structure of the records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Each record is followed by a newline.
+    JsonLines,
+    /// Each record is preceded by its length in bytes,
+    /// encoded as a 32-bit big-endian integer.
+    LengthPrefixed,
+}
+
+impl Framing {
+    /// Returns the frame containing the supplied payload.
+    pub fn encode(&self, payload : &[u8]) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(payload.len() + 4);
+        match self {
+            Framing::JsonLines => {
+                frame.extend_from_slice(payload);
+                frame.push(b'\n');
+            },
+            Framing::LengthPrefixed => {
+                frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+                frame.extend_from_slice(payload);
+            }
+        }
+        frame
+    }
+}
+
+/// Connection to a remote endpoint, shared with [`GelfSink`](super::GelfSink).
+pub(super) enum Connection {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+}
+
+impl Connection {
+    pub(super) fn open(addr : &str, transport : Transport) -> io::Result<Connection> {
+        let mut last_err = io::Error::new(io::ErrorKind::InvalidInput, format!("unable to resolve {}", addr));
+        for addr in addr.to_socket_addrs()? {
+            let result = match transport {
+                Transport::Tcp => TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).and_then(|stream| {
+                    stream.set_nodelay(true)?;
+                    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                    Ok(Connection::Tcp(stream))
+                }),
+                Transport::Udp => UdpSocket::bind(if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" }).and_then(|socket| {
+                    socket.connect(addr)?;
+                    Ok(Connection::Udp(socket))
+                })
+            };
+            match result {
+                Ok(connection) => return Ok(connection),
+                Err(err) => last_err = err
+            }
+        }
+        Err(last_err)
+    }
+
+    /// Returns `true` if the remote end has closed the TCP connection.
+    /// Writes to such a connection succeed locally, but the data is never
+    /// delivered, so the connection needs to be replaced before writing.
+    pub(super) fn is_closed(&self) -> bool {
+        match self {
+            Connection::Tcp(stream) => {
+                if stream.set_nonblocking(true).is_err() {
+                    return true;
+                }
+                let result = stream.peek(&mut [0]);
+                if stream.set_nonblocking(false).is_err() {
+                    return true;
+                }
+                match result {
+                    Ok(read) => read == 0,
+                    Err(err) => err.kind() != io::ErrorKind::WouldBlock
+                }
+            },
+            Connection::Udp(_) => false
+        }
+    }
+
+    pub(super) fn send(&mut self, frame : &[u8]) -> io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.write_all(frame),
+            Connection::Udp(socket) => socket.send(frame).map(|_| ()),
+        }
+    }
+}
+
+struct State {
+    frames : VecDeque<Vec<u8>>,
+    capacity : usize,
+    min_backoff : Duration,
+    max_backoff : Duration,
+    /// The worker is sending a frame
+    busy : bool,
+    /// The last attempt to deliver a frame has failed
+    failed : bool,
+    /// Number of failed attempts to deliver a frame
+    failures : u64,
+    /// Reconnect without waiting for the backoff delay to elapse
+    retry : bool,
+    shutdown : bool,
+    dropped : u64,
+}
+
+struct Shared {
+    addr : String,
+    transport : Transport,
+    state : Mutex<State>,
+    /// Signalled when frames are queued or shutdown is requested
+    pending : Condvar,
+    /// Signalled when the queue has been drained or delivery has failed
+    drained : Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+
+    fn run(&self) {
+        let mut connection : Option<Connection> = None;
+        let mut backoff = self.lock().min_backoff;
+        loop {
+            let frame = {
+                let mut state = self.lock();
+                while state.frames.is_empty() && !state.shutdown {
+                    state = wait(&self.pending, state);
+                }
+                match state.frames.pop_front() {
+                    Some(frame) => { state.busy = true; state.retry = false; frame },
+                    None => break
+                }
+            };
+
+            if connection.as_ref().is_some_and(Connection::is_closed) {
+                connection = None;
+            }
+            let result = match connection.as_mut() {
+                Some(connection) => connection.send(&frame),
+                None => Connection::open(&self.addr, self.transport).and_then(|mut opened| {
+                    opened.send(&frame)?;
+                    connection = Some(opened);
+                    Ok(())
+                })
+            };
+
+            let mut state = self.lock();
+            state.busy = false;
+            match result {
+                Ok(()) => {
+                    state.failed = false;
+                    backoff = state.min_backoff;
+                    if state.frames.is_empty() {
+                        self.drained.notify_all();
+                    }
+                },
+                Err(_) => {
+                    connection = None;
+                    state.failed = true;
+                    state.failures += 1;
+                    // datagrams are not retried, stream frames are resent once reconnected
+                    if self.transport == Transport::Tcp && state.frames.len() < state.capacity {
+                        state.frames.push_front(frame);
+                    } else {
+                        state.dropped += 1;
+                    }
+                    self.drained.notify_all();
+                    if state.shutdown {
+                        break;
+                    }
+
+                    let deadline = Instant::now() + backoff;
+                    backoff = (backoff * 2).min(state.max_backoff);
+                    while !state.shutdown && !state.retry {
+                        let now = Instant::now();
+                        if now >= deadline {
+                            break;
+                        }
+                        state = wait_timeout(&self.pending, state, deadline - now);
+                    }
+                }
+            }
+        }
+        self.drained.notify_all();
+    }
+}
+
+/// A [`Sink`] forwarding log messages to a remote collector over TCP or UDP,
+/// for example to a [`LogReceiver`](super::LogReceiver).
+///
+/// Each record is serialized as a single-line JSON object and framed as
+/// configured by [`Framing`]:
+///
+/// `{"timestamp":"2022-10-25T12:13:20.123Z","level":"INFO","target":"net",
+/// "module_path":"app::net","file":"src/net.rs","line":42,"message":"...",
+/// "fields":{"peer":"..."}}`
+///
+/// Records are queued and sent by a worker thread, which (re)connects
+/// to the collector as needed. While the collector is unreachable records
+/// are retained in a bounded buffer, discarding the oldest records once
+/// the buffer is full, and reconnection is retried with exponential backoff.
+/// Records that could not be sent over UDP are discarded.
+///
+/// Before sending a record over TCP, the worker checks whether the collector
+/// has closed the connection and reconnects if so. TCP reports a connection
+/// closed after this check only on a subsequent write, so records written
+/// while the collector is closing the connection can be lost.
+pub struct NetworkSink {
+    shared : Arc<Shared>,
+    framing : Framing,
+    worker : Mutex<Option<JoinHandle<()>>>,
+}
+
+impl NetworkSink {
+    /// Creates a sink sending records to `addr` (e.g. `"collector:9000"`) and
+    /// spawns its worker thread. The address is resolved on each connection
+    /// attempt. By default up to 10000 records are buffered and reconnection
+    /// backoff ranges from 100 milliseconds to 30 seconds.
+    pub fn new(addr : &str, transport : Transport, framing : Framing) -> NetworkSink {
+        let shared = Arc::new(Shared {
+            addr : addr.to_string(),
+            transport,
+            state : Mutex::new(State {
+                frames : VecDeque::new(),
+                capacity : 10_000,
+                min_backoff : Duration::from_millis(100),
+                max_backoff : Duration::from_secs(30),
+                busy : false,
+                failed : false,
+                failures : 0,
+                retry : false,
+                shutdown : false,
+                dropped : 0,
+            }),
+            pending : Condvar::new(),
+            drained : Condvar::new(),
+        });
+
+        let worker = {
+            let shared = shared.clone();
+            std::thread::Builder::new()
+                .name("workflow-log-network-sink".to_string())
+                .spawn(move || crate::in_sink_scope(|| shared.run()))
+                .expect("unable to spawn network sink worker thread")
+        };
+
+        NetworkSink {
+            shared,
+            framing,
+            worker : Mutex::new(Some(worker)),
+        }
+    }
+
+    /// Sets the maximum number of records buffered while the
+    /// collector is unreachable or slow.
+    pub fn with_capacity(self, capacity : usize) -> Self {
+        self.shared.lock().capacity = capacity.max(1);
+        self
+    }
+
+    /// Sets the minimum and maximum delay between reconnection attempts.
+    pub fn with_backoff(self, min : Duration, max : Duration) -> Self {
+        let mut state = self.shared.lock();
+        state.min_backoff = min;
+        state.max_backoff = max.max(min);
+        drop(state);
+        self
+    }
+
+    /// Returns the number of records discarded due to the buffer
+    /// being full or failing to be sent over UDP.
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+
+    /// Returns `true` if the last attempt to send a record has failed.
+    pub fn is_failed(&self) -> bool {
+        self.shared.lock().failed
+    }
+
+    /// Blocks until all buffered records have been sent or the collector
+    /// is found to be unreachable. If the worker is waiting to reconnect
+    /// following a failure, it reconnects immediately.
+    pub fn flush(&self) {
+        let mut state = self.shared.lock();
+        let failures = state.failures;
+        state.retry = true;
+        self.shared.pending.notify_all();
+        while (!state.frames.is_empty() || state.busy) && state.failures == failures && !state.shutdown {
+            state = wait(&self.shared.drained, state);
+        }
+    }
+
+    /// Attempts to send all buffered records and stops the worker thread.
+    /// Records received after shutdown are discarded.
+    pub fn shutdown(&self) {
+        self.shared.lock().shutdown = true;
+        self.shared.pending.notify_all();
+        if let Some(worker) = lock(&self.worker).take() {
+            worker.join().ok();
+        }
+    }
+}
+
+impl Sink for NetworkSink {
+    fn write_record(&self, record : &Record<'_>) -> SinkOutput {
+        let frame = self.framing.encode(format_record(record).as_bytes());
+        let mut state = self.shared.lock();
+        if state.shutdown {
+            return SinkOutput::PassThrough;
+        }
+        if state.frames.len() >= state.capacity {
+            state.frames.pop_front();
+            state.dropped += 1;
+        }
+        state.frames.push_back(frame);
+        self.shared.pending.notify_one();
+        SinkOutput::PassThrough
+    }
+
+    fn flush(&self) {
+        NetworkSink::flush(self);
+    }
+
+    fn on_remove(&self) {
+        NetworkSink::flush(self);
+    }
+}
+
+impl Drop for NetworkSink {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::write;
+    use crate::sinks::LogReceiver;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Collects the output of a [`LogReceiver`].
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf : &[u8]) -> io::Result<usize> {
+            lock(&self.0).extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        /// Waits until `count` records have been received and returns their messages.
+        fn messages(&self, count : usize) -> Vec<String> {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                let text = String::from_utf8(lock(&self.0).clone()).unwrap();
+                let lines = text.lines().map(|line| {
+                    let start = line.find("\"message\":\"").expect("record without message") + 11;
+                    line[start..start + line[start..].find('"').unwrap()].to_string()
+                }).collect::<Vec<_>>();
+                if lines.len() >= count || Instant::now() > deadline {
+                    return lines;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    fn receiver(addr : &str, transport : Transport, framing : Framing) -> (String, Output) {
+        let receiver = LogReceiver::bind(addr, transport, framing).unwrap();
+        let addr = receiver.local_addr().unwrap().to_string();
+        let output = Output::default();
+        let sink_output = output.clone();
+        std::thread::spawn(move || receiver.run(sink_output));
+        (addr, output)
+    }
+
+    #[test]
+    fn tcp() {
+        for framing in [Framing::JsonLines, Framing::LengthPrefixed] {
+            let (addr, output) = receiver("127.0.0.1:0", Transport::Tcp, framing);
+            let sink = NetworkSink::new(&addr, Transport::Tcp, framing);
+            for text in ["one", "two", "three"] {
+                write(&sink, text);
+            }
+            sink.flush();
+            assert!(!sink.is_failed());
+            assert_eq!(output.messages(3), ["one", "two", "three"]);
+        }
+    }
+
+    #[test]
+    fn udp() {
+        for framing in [Framing::JsonLines, Framing::LengthPrefixed] {
+            let (addr, output) = receiver("127.0.0.1:0", Transport::Udp, framing);
+            let sink = NetworkSink::new(&addr, Transport::Udp, framing);
+            for text in ["one", "two"] {
+                write(&sink, text);
+            }
+            sink.flush();
+            assert_eq!(output.messages(2), ["one", "two"]);
+        }
+    }
+
+    #[test]
+    fn buffering_while_unreachable() {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let sink = NetworkSink::new(&addr, Transport::Tcp, Framing::JsonLines)
+            .with_capacity(2)
+            .with_backoff(Duration::from_millis(200), Duration::from_millis(200));
+        write(&sink, "one");
+        while !sink.is_failed() {
+            std::thread::yield_now();
+        }
+        // flush does not wait for an unreachable collector
+        sink.flush();
+
+        // the oldest records are discarded while the worker waits to reconnect
+        for text in ["two", "three", "four"] {
+            write(&sink, text);
+        }
+        assert_eq!(sink.dropped(), 2);
+
+        // flush reconnects without waiting for the backoff delay
+        let (_, output) = receiver(&addr, Transport::Tcp, Framing::JsonLines);
+        sink.flush();
+        assert!(sink.shared.lock().frames.is_empty());
+        assert_eq!(output.messages(2), ["three", "four"]);
+        assert!(!sink.is_failed());
+        assert_eq!(sink.dropped(), 2);
+    }
+
+    #[test]
+    fn reconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let sink = NetworkSink::new(&addr, Transport::Tcp, Framing::JsonLines)
+            .with_backoff(Duration::from_millis(10), Duration::from_millis(10));
+
+        // the collector closes the first connection after receiving a record
+        write(&sink, "first");
+        let (stream, _) = listener.accept().unwrap();
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line).unwrap();
+        assert!(line.contains("\"message\":\"first\""));
+        drop(stream);
+
+        // records are sent until the sink detects the closed connection and reconnects
+        let sink = Arc::new(sink);
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (sink, stop) = (sink.clone(), stop.clone());
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    write(&*sink, "retry");
+                    std::thread::sleep(Duration::from_millis(20));
+                }
+            })
+        };
+        let (stream, _) = listener.accept().unwrap();
+        let mut line = String::new();
+        BufReader::new(&stream).read_line(&mut line).unwrap();
+        assert!(line.contains("\"message\":\"retry\""));
+        stop.store(true, Ordering::Relaxed);
+        writer.join().unwrap();
+    }
+
+    /// Accepts a connection, failing if none is made within 10 seconds.
+    fn accept(listener : &TcpListener) -> TcpStream {
+        listener.set_nonblocking(true).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false).unwrap();
+                    return stream;
+                },
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock && Instant::now() < deadline => {
+                    std::thread::sleep(Duration::from_millis(10));
+                },
+                Err(err) => panic!("no connection: {}", err)
+            }
+        }
+    }
+
+    fn read_line(stream : &TcpStream) -> String {
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        line
+    }
+
+    #[test]
+    fn reconnect_after_close() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let sink = NetworkSink::new(&addr, Transport::Tcp, Framing::JsonLines);
+        write(&sink, "first");
+        let stream = accept(&listener);
+        assert!(read_line(&stream).contains("\"message\":\"first\""));
+
+        // the record following the close is sent over a new connection
+        drop(stream);
+        std::thread::sleep(Duration::from_millis(50));
+        write(&sink, "second");
+        sink.flush();
+        assert!(read_line(&accept(&listener)).contains("\"message\":\"second\""));
+        assert!(!sink.is_failed());
+        assert_eq!(sink.dropped(), 0);
+    }
+}
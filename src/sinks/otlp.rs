@@ -0,0 +1,530 @@
+use super::json::write_string;
+use crate::sync::{lock, wait, wait_timeout};
+use crate::{impls, Level, Record, Sink, SinkOutput};
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, Once};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+/// Returns the OpenTelemetry severity number corresponding to the log level.
+pub fn otlp_severity(level : Level) -> u8 {
+    match level {
+        Level::Trace => 1,
+        Level::Debug => 5,
+        Level::Info => 9,
+        Level::Warn => 13,
+        Level::Error => 17,
+    }
+}
+
+struct State {
+    /// JSON-serialized OTLP `LogRecord`s
+    records : VecDeque<String>,
+    busy : bool,
+    flush : bool,
+    shutdown : bool,
+    /// The worker thread could not be started
+    stopped : bool,
+    exported : u64,
+    dropped : u64,
+}
+
+/// Settings of the sink, copied to the worker thread when it is started.
+#[derive(Clone)]
+struct Config {
+    endpoint : String,
+    headers : Vec<(String, String)>,
+    resource : Vec<(String, String)>,
+    batch_size : usize,
+    batch_interval : Duration,
+    capacity : usize,
+    timeout : Duration,
+    flush_timeout : Duration,
+}
+
+impl Config {
+    /// Builds an OTLP `ExportLogsServiceRequest` containing the records.
+    fn format_request(&self, records : &[String]) -> String {
+        let resource = self.resource.iter()
+            .map(|(key, value)| attribute(key, value))
+            .collect::<Vec<_>>();
+        let mut out = String::from("{\"resourceLogs\":[{\"resource\":{\"attributes\":[");
+        out.push_str(&resource.join(","));
+        out.push_str("]},\"scopeLogs\":[{\"scope\":{\"name\":\"workflow-log\",\"version\":\"");
+        out.push_str(env!("CARGO_PKG_VERSION"));
+        out.push_str("\"},\"logRecords\":[");
+        out.push_str(&records.join(","));
+        out.push_str("]}]}]}");
+        out
+    }
+}
+
+struct Shared {
+    state : Mutex<State>,
+    /// Signalled when records are queued or a flush or shutdown is requested
+    pending : Condvar,
+    /// Signalled when the worker has exported all queued records
+    drained : Condvar,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+
+    fn run(&self, config : &Config) {
+        let agent = ureq::AgentBuilder::new().timeout(config.timeout).build();
+        loop {
+            let batch = {
+                let mut state = self.lock();
+                let mut deadline : Option<Instant> = None;
+                loop {
+                    if state.records.is_empty() {
+                        if state.shutdown {
+                            self.drained.notify_all();
+                            return;
+                        }
+                        deadline = None;
+                        state = wait(&self.pending, state);
+                        continue;
+                    }
+                    if state.records.len() >= config.batch_size || state.flush || state.shutdown {
+                        break;
+                    }
+                    let deadline = *deadline.get_or_insert_with(|| Instant::now() + config.batch_interval);
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    state = wait_timeout(&self.pending, state, deadline - now);
+                }
+                state.busy = true;
+                let len = state.records.len().min(config.batch_size);
+                state.records.drain(..len).collect::<Vec<_>>()
+            };
+
+            let body = config.format_request(&batch);
+            let mut request = agent.post(&config.endpoint).set("Content-Type", "application/json");
+            for (name, value) in config.headers.iter() {
+                request = request.set(name, value);
+            }
+            let result = request.send_string(&body);
+
+            let mut state = self.lock();
+            state.busy = false;
+            match result {
+                Ok(_) => state.exported += batch.len() as u64,
+                Err(_) => state.dropped += batch.len() as u64,
+            }
+            if state.records.is_empty() {
+                state.flush = false;
+                self.drained.notify_all();
+            }
+        }
+    }
+}
+
+/// A [`Sink`] exporting log messages to an OpenTelemetry collector
+/// using the OTLP/HTTP protocol with JSON encoding (requires the
+/// `otlp` feature). Exporting to `https://` endpoints additionally
+/// requires the `otlp-tls` feature.
+///
+/// Each message is converted to an OTLP `LogRecord` with its level mapped
+/// to a severity number using [`otlp_severity`]. The record target, location
+/// and structured fields are attached as attributes. Records are exported
+/// in batches by a worker thread, started when the first message is received,
+/// once the batch is full or the batch interval has elapsed since the first
+/// record of the batch has been received. The `service.name` resource
+/// attribute and any additional resource attributes are attached to
+/// each export request.
+///
+/// Batches that fail to be exported are discarded and counted by
+/// [`OtlpSink::dropped`], as are records received while the queue is full.
+/// Should the worker thread fail to start, the failure is reported on the
+/// console and all records are discarded.
+pub struct OtlpSink {
+    config : Config,
+    shared : Arc<Shared>,
+    start : Once,
+    worker : Mutex<Option<JoinHandle<()>>>,
+}
+
+impl OtlpSink {
+    /// Creates a sink exporting to the OTLP/HTTP logs `endpoint`,
+    /// e.g. `http://localhost:4318/v1/logs`. By default records are exported
+    /// in batches of up to 512 records every second, up to 10000 records
+    /// are queued and flushing waits for up to 5 seconds. `service.name`
+    /// defaults to `unknown_service:<executable>`.
+    pub fn new(endpoint : &str) -> OtlpSink {
+        let service_name = std::env::current_exe().ok()
+            .and_then(|path| path.file_name().map(|name| format!("unknown_service:{}", name.to_string_lossy())))
+            .unwrap_or_else(|| "unknown_service".to_string());
+        OtlpSink {
+            config : Config {
+                endpoint : endpoint.to_string(),
+                headers : Vec::new(),
+                resource : vec![("service.name".to_string(), service_name)],
+                batch_size : 512,
+                batch_interval : Duration::from_secs(1),
+                capacity : 10_000,
+                timeout : Duration::from_secs(10),
+                flush_timeout : Duration::from_secs(5),
+            },
+            shared : Arc::new(Shared {
+                state : Mutex::new(State {
+                    records : VecDeque::new(),
+                    busy : false,
+                    flush : false,
+                    shutdown : false,
+                    stopped : false,
+                    exported : 0,
+                    dropped : 0,
+                }),
+                pending : Condvar::new(),
+                drained : Condvar::new(),
+            }),
+            start : Once::new(),
+            worker : Mutex::new(None),
+        }
+    }
+
+    /// Sets the `service.name` resource attribute.
+    pub fn with_service_name(self, service_name : &str) -> Self {
+        self.with_resource_attribute("service.name", service_name)
+    }
+
+    /// Sets a resource attribute (e.g. `service.version`, `deployment.environment`)
+    /// describing the source of the records.
+    pub fn with_resource_attribute(mut self, key : &str, value : &str) -> Self {
+        let resource = &mut self.config.resource;
+        resource.retain(|(existing, _)| existing != key);
+        resource.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Adds an HTTP header (e.g. for authentication) to the export requests.
+    pub fn with_header(mut self, name : &str, value : &str) -> Self {
+        self.config.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets the maximum number of records exported in a single request.
+    pub fn with_batch_size(mut self, batch_size : usize) -> Self {
+        self.config.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Sets the maximum time records are held before being exported.
+    pub fn with_batch_interval(mut self, batch_interval : Duration) -> Self {
+        self.config.batch_interval = batch_interval;
+        self
+    }
+
+    /// Sets the maximum number of records waiting to be exported.
+    pub fn with_capacity(mut self, capacity : usize) -> Self {
+        self.config.capacity = capacity.max(1);
+        self
+    }
+
+    /// Sets the timeout of the export requests.
+    pub fn with_timeout(mut self, timeout : Duration) -> Self {
+        self.config.timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum time [`OtlpSink::flush`] waits for the export
+    /// of the queued records, e.g. while the collector is unresponsive.
+    pub fn with_flush_timeout(mut self, flush_timeout : Duration) -> Self {
+        self.config.flush_timeout = flush_timeout;
+        self
+    }
+
+    /// Returns the number of records exported successfully.
+    pub fn exported(&self) -> u64 {
+        self.shared.lock().exported
+    }
+
+    /// Returns the number of records discarded due to the queue
+    /// being full or failing to be exported.
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+
+    /// Exports all queued records, blocking until the export has completed
+    /// or the flush timeout (see [`OtlpSink::with_flush_timeout`]) has elapsed.
+    /// Records that have not been exported by then remain queued.
+    pub fn flush(&self) {
+        let mut state = self.shared.lock();
+        if !state.records.is_empty() {
+            state.flush = true;
+            self.shared.pending.notify_one();
+        }
+        let deadline = Instant::now() + self.config.flush_timeout;
+        while (!state.records.is_empty() || state.busy) && !state.stopped {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = wait_timeout(&self.shared.drained, state, deadline - now);
+        }
+    }
+
+    /// Exports all queued records and stops the worker thread.
+    /// Records received after shutdown are discarded.
+    pub fn shutdown(&self) {
+        self.shared.lock().shutdown = true;
+        self.shared.pending.notify_all();
+        if let Some(worker) = lock(&self.worker).take() {
+            worker.join().ok();
+        }
+    }
+
+    fn start_worker(&self) {
+        self.start.call_once(|| {
+            let shared = self.shared.clone();
+            let config = self.config.clone();
+            let result = std::thread::Builder::new()
+                .name("workflow-log-otlp-sink".to_string())
+                .spawn(move || crate::in_sink_scope(|| shared.run(&config)));
+            match result {
+                Ok(worker) => *lock(&self.worker) = Some(worker),
+                Err(err) => {
+                    self.shared.lock().stopped = true;
+                    impls::console_impl(Level::Error, &format_args!("workflow-log: unable to start the OTLP sink worker thread: {}", err));
+                }
+            }
+        });
+    }
+}
+
+impl Sink for OtlpSink {
+    fn write_record(&self, record : &Record<'_>) -> SinkOutput {
+        let log_record = format_log_record(record);
+        self.start_worker();
+        let mut state = self.shared.lock();
+        if state.shutdown {
+            return SinkOutput::PassThrough;
+        }
+        if state.stopped {
+            state.dropped += 1;
+            return SinkOutput::PassThrough;
+        }
+        if state.records.len() >= self.config.capacity {
+            state.records.pop_front();
+            state.dropped += 1;
+        }
+        state.records.push_back(log_record);
+        // wake the worker to start the batch interval or export a full batch
+        if state.records.len() == 1 || state.records.len() >= self.config.batch_size {
+            self.shared.pending.notify_one();
+        }
+        SinkOutput::PassThrough
+    }
+
+    fn flush(&self) {
+        OtlpSink::flush(self);
+    }
+
+    fn on_remove(&self) {
+        OtlpSink::flush(self);
+    }
+}
+
+impl Drop for OtlpSink {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Serializes an OTLP `KeyValue` with a string value.
+fn attribute(key : &str, value : &str) -> String {
+    let mut out = String::from("{\"key\":");
+    write_string(&mut out, key);
+    out.push_str(",\"value\":{\"stringValue\":");
+    write_string(&mut out, value);
+    out.push_str("}}");
+    out
+}
+
+/// Serializes the record as an OTLP `LogRecord` JSON object.
+fn format_log_record(record : &Record<'_>) -> String {
+    let mut attributes = Vec::new();
+    if !record.target.is_empty() {
+        attributes.push(attribute("target", record.target));
+    }
+    if let Some(module_path) = record.module_path {
+        attributes.push(attribute("code.namespace", module_path));
+    }
+    if let Some(file) = record.file {
+        attributes.push(attribute("code.filepath", file));
+    }
+    if let Some(line) = record.line {
+        attributes.push(format!("{{\"key\":\"code.lineno\",\"value\":{{\"intValue\":\"{}\"}}}}", line));
+    }
+    for field in record.fields.iter() {
+        attributes.push(attribute(field.key, &field.value.to_string()));
+    }
+
+    let time = record.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let mut out = String::with_capacity(256);
+    let _ = write!(out, "{{\"timeUnixNano\":\"{}\",\"observedTimeUnixNano\":\"{}\",\"severityNumber\":{},\"severityText\":\"{}\",\"body\":{{\"stringValue\":",
+        time, time, otlp_severity(record.level), record.level.as_str());
+    write_string(&mut out, &record.args.to_string());
+    out.push_str("},\"attributes\":[");
+    out.push_str(&attributes.join(","));
+    out.push_str("]}");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{channel, Receiver};
+
+    struct Request {
+        headers : Vec<String>,
+        body : String,
+    }
+
+    /// Starts an HTTP server responding to all requests with `status`
+    /// and returns its endpoint along with the received requests.
+    fn server(status : u16) -> (String, Receiver<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/logs", listener.local_addr().unwrap());
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                let sender = sender.clone();
+                std::thread::spawn(move || {
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut stream = stream;
+                    loop {
+                        let mut headers = Vec::new();
+                        loop {
+                            let mut line = String::new();
+                            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                                return;
+                            }
+                            let line = line.trim_end().to_string();
+                            if line.is_empty() {
+                                break;
+                            }
+                            headers.push(line);
+                        }
+                        let len = headers.iter()
+                            .find_map(|header| header.to_ascii_lowercase().strip_prefix("content-length:").map(|len| len.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        let mut body = vec![0; len];
+                        reader.read_exact(&mut body).unwrap();
+                        write!(stream, "HTTP/1.1 {} Status\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+                        if sender.send(Request { headers, body : String::from_utf8(body).unwrap() }).is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (endpoint, receiver)
+    }
+
+    fn log(sink : &OtlpSink, text : &str) {
+        sink.write_record(&Record { target : "server", ..Record::new(Level::Warn, format_args!("{}", text)) });
+    }
+
+    #[test]
+    fn export() {
+        let (endpoint, requests) = server(200);
+        let sink = OtlpSink::new(&endpoint)
+            .with_service_name("service")
+            .with_header("Authorization", "Bearer token")
+            .with_batch_size(2)
+            .with_batch_interval(Duration::from_secs(60));
+
+        log(&sink, "first");
+        log(&sink, "second \"quoted\"");
+        let request = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(request.headers[0].starts_with("POST /v1/logs "));
+        assert!(request.headers.iter().any(|header| header == "Authorization: Bearer token"));
+        assert!(request.headers.iter().any(|header| header == "Content-Type: application/json"));
+        assert!(request.body.starts_with(
+            "{\"resourceLogs\":[{\"resource\":{\"attributes\":[{\"key\":\"service.name\",\"value\":{\"stringValue\":\"service\"}}]},\"scopeLogs\":[{\"scope\":{\"name\":\"workflow-log\""),
+            "{}", request.body);
+        assert_eq!(request.body.matches("\"severityNumber\":13,\"severityText\":\"WARN\"").count(), 2);
+        assert!(request.body.contains("\"body\":{\"stringValue\":\"first\"},\"attributes\":[{\"key\":\"target\",\"value\":{\"stringValue\":\"server\"}}]"));
+        assert!(request.body.contains("\"body\":{\"stringValue\":\"second \\\"quoted\\\"\"}"));
+
+        // the batch is not full, the record is exported by the flush
+        log(&sink, "third");
+        sink.flush();
+        let request = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(request.body.matches("\"timeUnixNano\"").count(), 1);
+        assert!(request.body.contains("\"stringValue\":\"third\""));
+        assert_eq!(sink.exported(), 3);
+        assert_eq!(sink.dropped(), 0);
+    }
+
+    #[test]
+    fn batch_interval() {
+        let (endpoint, requests) = server(200);
+        let sink = OtlpSink::new(&endpoint).with_batch_interval(Duration::from_millis(50));
+        log(&sink, "message");
+        let request = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(request.body.contains("\"stringValue\":\"message\""));
+    }
+
+    #[test]
+    fn failed_export() {
+        let (endpoint, requests) = server(500);
+        let sink = OtlpSink::new(&endpoint).with_batch_interval(Duration::from_secs(60));
+        log(&sink, "first");
+        log(&sink, "second");
+        sink.flush();
+        assert!(requests.recv_timeout(Duration::from_secs(10)).is_ok());
+        assert_eq!(sink.exported(), 0);
+        assert_eq!(sink.dropped(), 2);
+    }
+
+    #[test]
+    fn full_queue() {
+        let (endpoint, requests) = server(200);
+        let sink = OtlpSink::new(&endpoint)
+            .with_capacity(2)
+            .with_batch_interval(Duration::from_secs(60));
+        log(&sink, "first");
+        log(&sink, "second");
+        log(&sink, "third");
+        assert_eq!(sink.dropped(), 1);
+
+        // the oldest record is discarded, records received after shutdown are ignored
+        sink.shutdown();
+        log(&sink, "fourth");
+        let request = requests.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(!request.body.contains("\"stringValue\":\"first\""));
+        assert!(request.body.contains("\"stringValue\":\"second\""));
+        assert!(request.body.contains("\"stringValue\":\"third\""));
+        assert_eq!(sink.exported(), 2);
+        assert_eq!(sink.dropped(), 1);
+    }
+
+    #[test]
+    fn flush_timeout() {
+        // the collector accepts connections but never responds
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/logs", listener.local_addr().unwrap());
+        let sink = OtlpSink::new(&endpoint)
+            .with_batch_interval(Duration::from_secs(60))
+            .with_flush_timeout(Duration::from_millis(100));
+        log(&sink, "message");
+        let start = Instant::now();
+        sink.flush();
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(sink.exported(), 0);
+
+        // closing the listener resets the pending request, so that the sink can shut down
+        drop(listener);
+    }
+}
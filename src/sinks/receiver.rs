@@ -0,0 +1,132 @@
+use super::network::{Framing, Transport};
+use crate::sync::lock;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+
+/// Maximum size of a record accepted by [`LogReceiver`].
+const MAX_FRAME_SIZE : usize = 16 * 1024 * 1024;
+
+enum Socket {
+    Tcp(TcpListener),
+    Udp(UdpSocket),
+}
+
+/// Receives records sent by [`NetworkSink`](super::NetworkSink) instances
+/// (possibly running in multiple processes) and writes them to an output,
+/// such as `stdout` or a file, as JSON lines.
+///
+/// ```no_run
+/// use workflow_log::sinks::*;
+/// let receiver = LogReceiver::bind("0.0.0.0:9000", Transport::Tcp, Framing::JsonLines)?;
+/// receiver.run(std::io::stdout())?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct LogReceiver {
+    socket : Socket,
+    framing : Framing,
+}
+
+impl LogReceiver {
+    /// Binds the receiver to the supplied address.
+    pub fn bind<A : ToSocketAddrs>(addr : A, transport : Transport, framing : Framing) -> io::Result<LogReceiver> {
+        let socket = match transport {
+            Transport::Tcp => Socket::Tcp(TcpListener::bind(addr)?),
+            Transport::Udp => Socket::Udp(UdpSocket::bind(addr)?),
+        };
+        Ok(LogReceiver { socket, framing })
+    }
+
+    /// Returns the address the receiver is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match &self.socket {
+            Socket::Tcp(listener) => listener.local_addr(),
+            Socket::Udp(socket) => socket.local_addr(),
+        }
+    }
+
+    /// Receives records, writing each of them to `output` followed by a
+    /// newline. TCP connections are served by separate threads. Runs until
+    /// the socket fails; errors of individual connections and malformed
+    /// datagrams are ignored.
+    pub fn run<W : Write + Send + 'static>(&self, output : W) -> io::Result<()> {
+        let output = Arc::new(Mutex::new(output));
+        match &self.socket {
+            Socket::Tcp(listener) => {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(_) => continue
+                    };
+                    let output = output.clone();
+                    let framing = self.framing;
+                    std::thread::Builder::new()
+                        .name("workflow-log-receiver".to_string())
+                        .spawn(move || receive_stream(stream, framing, &output))?;
+                }
+                Ok(())
+            },
+            Socket::Udp(socket) => {
+                let mut buffer = vec![0u8; 65536];
+                loop {
+                    let len = socket.recv(&mut buffer)?;
+                    if let Some(record) = decode_datagram(&buffer[..len], self.framing) {
+                        write_record(&output, record)?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn receive_stream<W : Write>(stream : TcpStream, framing : Framing, output : &Mutex<W>) -> io::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut buffer = Vec::new();
+    loop {
+        buffer.clear();
+        match framing {
+            Framing::JsonLines => {
+                let len = reader.by_ref().take(MAX_FRAME_SIZE as u64 + 1).read_until(b'\n', &mut buffer)?;
+                if len == 0 {
+                    return Ok(());
+                }
+                if len > MAX_FRAME_SIZE && !buffer.ends_with(b"\n") {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "record exceeds maximum frame size"));
+                }
+            },
+            Framing::LengthPrefixed => {
+                let mut len = [0u8; 4];
+                match reader.read_exact(&mut len) {
+                    Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+                    result => result?
+                }
+                let len = u32::from_be_bytes(len) as usize;
+                if len > MAX_FRAME_SIZE {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "record exceeds maximum frame size"));
+                }
+                buffer.resize(len, 0);
+                reader.read_exact(&mut buffer)?;
+            }
+        }
+        write_record(output, &buffer)?;
+    }
+}
+
+/// Returns the record contained in a datagram.
+fn decode_datagram(datagram : &[u8], framing : Framing) -> Option<&[u8]> {
+    match framing {
+        Framing::JsonLines => Some(datagram),
+        Framing::LengthPrefixed => {
+            let (len, payload) = datagram.split_first_chunk::<4>()?;
+            (u32::from_be_bytes(*len) as usize == payload.len()).then_some(payload)
+        }
+    }
+}
+
+fn write_record<W : Write>(output : &Mutex<W>, record : &[u8]) -> io::Result<()> {
+    let record = record.strip_suffix(b"\n").unwrap_or(record);
+    let mut output = lock(output);
+    output.write_all(record)?;
+    output.write_all(b"\n")?;
+    output.flush()
+}
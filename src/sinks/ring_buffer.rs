@@ -0,0 +1,232 @@
+use crate::{LevelFilter, LogEntry, Record, Sink, SinkOutput};
+use crate::sync::lock;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Criteria used to select [`LogEntry`] records from a [`RingBufferSink`].
+/// An empty query matches all entries.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    level : Option<LevelFilter>,
+    target : Option<String>,
+    from : Option<SystemTime>,
+    to : Option<SystemTime>,
+    text : Option<String>,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Matches entries allowed by the supplied [`LevelFilter`].
+    pub fn with_level(mut self, level : LevelFilter) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Matches entries whose target starts with the supplied prefix.
+    pub fn with_target(mut self, target : &str) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    /// Matches entries logged within `from..to`. Either bound can be omitted.
+    pub fn with_time_range(mut self, from : Option<SystemTime>, to : Option<SystemTime>) -> Self {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    /// Matches entries containing the supplied text.
+    pub fn with_text(mut self, text : &str) -> Self {
+        self.text = Some(text.to_string());
+        self
+    }
+
+    /// Returns `true` if the entry satisfies all criteria of the query.
+    pub fn matches(&self, entry : &LogEntry) -> bool {
+        self.level.is_none_or(|level| entry.level <= level)
+            && self.target.as_ref().is_none_or(|target| entry.target.starts_with(target.as_str()))
+            && self.from.is_none_or(|from| entry.timestamp >= from)
+            && self.to.is_none_or(|to| entry.timestamp < to)
+            && self.text.as_ref().is_none_or(|text| entry.text.contains(text.as_str()))
+    }
+}
+
+struct Buffer {
+    entries : VecDeque<LogEntry>,
+    bytes : usize,
+}
+
+/// A [`Sink`] retaining the most recent log messages in memory.
+///
+/// The buffer holds at most `max_entries` messages and, if configured,
+/// at most `max_bytes` of message text; the oldest messages are discarded
+/// when either limit is exceeded. Retained messages can be retrieved
+/// using [`RingBufferSink::snapshot`], [`RingBufferSink::drain`] or
+/// [`RingBufferSink::query`].
+pub struct RingBufferSink {
+    max_entries : usize,
+    max_bytes : Option<usize>,
+    buffer : Mutex<Buffer>,
+}
+
+impl RingBufferSink {
+    /// Creates a buffer retaining at most `max_entries` messages.
+    pub fn new(max_entries : usize) -> RingBufferSink {
+        RingBufferSink {
+            max_entries,
+            max_bytes : None,
+            buffer : Mutex::new(Buffer { entries : VecDeque::new(), bytes : 0 }),
+        }
+    }
+
+    /// Limits the total size of the retained message text.
+    pub fn with_max_bytes(mut self, max_bytes : usize) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Returns the number of retained messages.
+    pub fn len(&self) -> usize {
+        lock(&self.buffer).entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a copy of all retained messages, oldest first.
+    pub fn snapshot(&self) -> Vec<LogEntry> {
+        lock(&self.buffer).entries.iter().cloned().collect()
+    }
+
+    /// Removes and returns all retained messages, oldest first.
+    pub fn drain(&self) -> Vec<LogEntry> {
+        let mut buffer = lock(&self.buffer);
+        buffer.bytes = 0;
+        buffer.entries.drain(..).collect()
+    }
+
+    /// Returns a copy of the retained messages matching the query, oldest first.
+    pub fn query(&self, query : &Query) -> Vec<LogEntry> {
+        lock(&self.buffer).entries.iter().filter(|entry| query.matches(entry)).cloned().collect()
+    }
+
+    /// Discards all retained messages.
+    pub fn clear(&self) {
+        self.drain();
+    }
+}
+
+impl Sink for RingBufferSink {
+    fn write_record(&self, record : &Record<'_>) -> SinkOutput {
+        if self.max_entries == 0 {
+            return SinkOutput::PassThrough;
+        }
+        let entry = LogEntry::from(record);
+        let mut buffer = lock(&self.buffer);
+        buffer.bytes += entry.text.len();
+        buffer.entries.push_back(entry);
+        let max_bytes = self.max_bytes.unwrap_or(usize::MAX);
+        while buffer.entries.len() > self.max_entries || (buffer.bytes > max_bytes && buffer.entries.len() > 1) {
+            if let Some(entry) = buffer.entries.pop_front() {
+                buffer.bytes -= entry.text.len();
+            }
+        }
+        SinkOutput::PassThrough
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Level;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn write(sink : &RingBufferSink, level : Level, target : &str, secs : u64, text : &str) {
+        sink.write_record(&Record {
+            target,
+            timestamp : UNIX_EPOCH + Duration::from_secs(secs),
+            ..Record::new(level, format_args!("{}", text))
+        });
+    }
+
+    fn texts(entries : Vec<LogEntry>) -> Vec<String> {
+        entries.into_iter().map(|entry| entry.text).collect()
+    }
+
+    #[test]
+    fn max_entries() {
+        let sink = RingBufferSink::new(3);
+        for text in ["one", "two", "three", "four", "five"] {
+            write(&sink, Level::Info, "", 0, text);
+        }
+        assert_eq!(sink.len(), 3);
+        assert_eq!(texts(sink.snapshot()), ["three", "four", "five"]);
+        assert_eq!(texts(sink.drain()), ["three", "four", "five"]);
+        assert!(sink.is_empty());
+
+        let sink = RingBufferSink::new(0);
+        write(&sink, Level::Info, "", 0, "one");
+        assert!(sink.is_empty());
+    }
+
+    #[test]
+    fn max_bytes() {
+        let sink = RingBufferSink::new(100).with_max_bytes(10);
+        for text in ["aaaa", "bbbb", "cccc"] {
+            write(&sink, Level::Info, "", 0, text);
+        }
+        assert_eq!(texts(sink.snapshot()), ["bbbb", "cccc"]);
+        // the most recent message is retained even if it exceeds the limit
+        write(&sink, Level::Info, "", 0, "dddddddddddd");
+        assert_eq!(texts(sink.snapshot()), ["dddddddddddd"]);
+        // the size is reset when the buffer is drained
+        sink.clear();
+        for text in ["aaaa", "bbbb"] {
+            write(&sink, Level::Info, "", 0, text);
+        }
+        assert_eq!(texts(sink.snapshot()), ["aaaa", "bbbb"]);
+    }
+
+    #[test]
+    fn query() {
+        let sink = RingBufferSink::new(100);
+        write(&sink, Level::Error, "server::http", 10, "request failed");
+        write(&sink, Level::Info, "server::http", 20, "request served");
+        write(&sink, Level::Warn, "client", 30, "request retried");
+        write(&sink, Level::Debug, "server", 40, "connection closed");
+
+        assert_eq!(sink.query(&Query::new()).len(), 4);
+        assert_eq!(texts(sink.query(&Query::new().with_level(LevelFilter::Warn))), ["request failed", "request retried"]);
+        assert_eq!(texts(sink.query(&Query::new().with_target("server"))), ["request failed", "request served", "connection closed"]);
+        assert_eq!(texts(sink.query(&Query::new().with_target("server::http").with_text("served"))), ["request served"]);
+        let from = Some(UNIX_EPOCH + Duration::from_secs(20));
+        let to = Some(UNIX_EPOCH + Duration::from_secs(40));
+        assert_eq!(texts(sink.query(&Query::new().with_time_range(from, to))), ["request served", "request retried"]);
+        assert_eq!(texts(sink.query(&Query::new().with_time_range(None, from))), ["request failed"]);
+        assert_eq!(texts(sink.query(&Query::new().with_time_range(to, None).with_level(LevelFilter::Info))), Vec::<String>::new());
+        // queries do not remove entries
+        assert_eq!(sink.len(), 4);
+    }
+
+    #[test]
+    fn poisoned_buffer() {
+        let sink = RingBufferSink::new(3);
+        write(&sink, Level::Info, "", 0, "before");
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _buffer = sink.buffer.lock().unwrap();
+            panic!("failure");
+        }));
+        assert!(result.is_err());
+        assert!(sink.buffer.is_poisoned());
+
+        write(&sink, Level::Info, "", 0, "after");
+        assert_eq!(texts(sink.snapshot()), ["before", "after"]);
+        assert_eq!(texts(sink.drain()), ["before", "after"]);
+        assert!(sink.is_empty());
+    }
+}
@@ -0,0 +1,266 @@
+use super::clock::{Clock, SystemClock};
+use super::datetime::{civil_from_days, days_from_civil};
+use super::file::{format_line, open};
+use crate::{Record, Sink, SinkOutput};
+use crate::sync::lock;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Period covered by each file created by [`TimeRotatingFileSink`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Hourly,
+    Daily,
+}
+
+impl Rotation {
+    fn seconds(&self) -> u64 {
+        match self {
+            Rotation::Hourly => 60 * 60,
+            Rotation::Daily => 24 * 60 * 60,
+        }
+    }
+
+    fn format(&self, period : u64) -> String {
+        let secs = period * self.seconds();
+        let (year, month, day) = civil_from_days((secs / 86400) as i64);
+        match self {
+            Rotation::Hourly => format!("{:04}-{:02}-{:02}-{:02}", year, month, day, secs % 86400 / 3600),
+            Rotation::Daily => format!("{:04}-{:02}-{:02}", year, month, day),
+        }
+    }
+
+    fn parse(&self, text : &str) -> Option<u64> {
+        let parts = text.split('-').map(|part| part.parse::<u32>().ok()).collect::<Option<Vec<_>>>()?;
+        let (days, hour) = match (self, parts.as_slice()) {
+            (Rotation::Hourly, [year, month, day, hour]) => (days_from_civil(*year as i64, *month, *day), *hour as u64),
+            (Rotation::Daily, [year, month, day]) => (days_from_civil(*year as i64, *month, *day), 0),
+            _ => return None
+        };
+        let secs = u64::try_from(days).ok()? * 86400 + hour * 3600;
+        Some(secs / self.seconds())
+    }
+}
+
+struct RollingState {
+    period : Option<u64>,
+    file : Option<File>,
+}
+
+/// A [`Sink`] that writes log messages to a new file every hour or day.
+///
+/// Files are created in the configured directory and named after the
+/// supplied prefix and the (UTC) date of the period they cover, for
+/// example `app.log.2022-10-25` or, for hourly rotation, `app.log.2022-10-25-13`.
+///
+/// If a maximum age is configured, files whose period ended more than
+/// the maximum age ago are deleted whenever a new file is opened.
+/// Files that can not be deleted are skipped without affecting logging.
+pub struct TimeRotatingFileSink {
+    directory : PathBuf,
+    prefix : String,
+    rotation : Rotation,
+    max_age : Option<Duration>,
+    clock : Arc<dyn Clock>,
+    state : Mutex<RollingState>,
+}
+
+impl TimeRotatingFileSink {
+    /// Creates a sink writing files named `<prefix>.<date>` into `directory`.
+    /// The directory is created if it does not exist.
+    pub fn new<P : AsRef<Path>>(directory : P, prefix : &str, rotation : Rotation) -> io::Result<TimeRotatingFileSink> {
+        let directory = directory.as_ref().to_path_buf();
+        fs::create_dir_all(&directory)?;
+        Ok(TimeRotatingFileSink {
+            directory,
+            prefix : prefix.to_string(),
+            rotation,
+            max_age : None,
+            clock : Arc::new(SystemClock),
+            state : Mutex::new(RollingState { period : None, file : None }),
+        })
+    }
+
+    /// Deletes files whose period ended more than `max_age` ago.
+    pub fn with_max_age(mut self, max_age : Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Uses the supplied [`Clock`] instead of the system time.
+    pub fn with_clock(mut self, clock : Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Returns the path of the file receiving messages at the current time.
+    pub fn current_path(&self) -> PathBuf {
+        self.path(self.period(self.clock.now()))
+    }
+
+    /// Deletes files older than the configured maximum age. Files that can
+    /// not be deleted are skipped and the first error encountered is returned.
+    pub fn cleanup(&self) -> io::Result<()> {
+        self.cleanup_impl(self.clock.now())
+    }
+
+    fn period(&self, time : SystemTime) -> u64 {
+        seconds(time) / self.rotation.seconds()
+    }
+
+    fn path(&self, period : u64) -> PathBuf {
+        self.directory.join(format!("{}.{}", self.prefix, self.rotation.format(period)))
+    }
+
+    fn cleanup_impl(&self, time : SystemTime) -> io::Result<()> {
+        let max_age = match self.max_age {
+            Some(max_age) => max_age.as_secs(),
+            None => return Ok(())
+        };
+        let seconds = self.rotation.seconds();
+        let now = self::seconds(time);
+        let current = now / seconds;
+        let prefix = format!("{}.", self.prefix);
+        let mut result = Ok(());
+        for entry in fs::read_dir(&self.directory)? {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => { result = result.and(Err(err)); continue; }
+            };
+            let name = entry.file_name();
+            let period = name.to_str()
+                .and_then(|name| name.strip_prefix(&prefix))
+                .and_then(|date| self.rotation.parse(date));
+            if let Some(period) = period {
+                if period < current && (period + 1) * seconds + max_age <= now {
+                    if let Err(err) = fs::remove_file(entry.path()) {
+                        result = result.and(Err(err));
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    fn write_impl(&self, state : &mut RollingState, line : &str) -> io::Result<()> {
+        let now = self.clock.now();
+        let period = self.period(now);
+        let opened = state.period != Some(period) || state.file.is_none();
+        if opened {
+            state.file = None;
+            state.file = Some(open(&self.path(period))?);
+            state.period = Some(period);
+        }
+        if let Some(file) = state.file.as_mut() {
+            file.write_all(line.as_bytes())?;
+        }
+        // deleting old files is best-effort and must not cause the
+        // message to be lost; failures are reported by `cleanup()`
+        if opened {
+            let _ = self.cleanup_impl(now);
+        }
+        Ok(())
+    }
+}
+
+fn seconds(time : SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+impl Sink for TimeRotatingFileSink {
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        let line = format_line(record);
+        let mut state = lock(&self.state);
+        self.write_impl(&mut state, &line)?;
+        Ok(SinkOutput::PassThrough)
+    }
+
+    /// Closes the log file. It is reopened if the sink receives further messages.
+    fn on_remove(&self) {
+        lock(&self.state).file.take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::{directory, write, MockClock};
+
+    /// 2023-11-05T00:00:00Z
+    const MIDNIGHT : u64 = 1_699_142_400;
+
+    fn files(directory : &Path) -> Vec<String> {
+        let mut files = fs::read_dir(directory).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn daily_rotation() {
+        let directory = directory("rolling-daily");
+        let clock = MockClock::new(MIDNIGHT - 60);
+        let sink = TimeRotatingFileSink::new(&directory, "app.log", Rotation::Daily).unwrap()
+            .with_clock(clock.clone());
+        assert_eq!(sink.current_path(), directory.join("app.log.2023-11-04"));
+        write(&sink, "before midnight");
+        clock.set(MIDNIGHT + 60);
+        assert_eq!(sink.current_path(), directory.join("app.log.2023-11-05"));
+        write(&sink, "after midnight");
+
+        assert_eq!(files(&directory), ["app.log.2023-11-04", "app.log.2023-11-05"]);
+        let before = fs::read_to_string(directory.join("app.log.2023-11-04")).unwrap();
+        let after = fs::read_to_string(directory.join("app.log.2023-11-05")).unwrap();
+        assert!(before.contains("before midnight") && !before.contains("after midnight"));
+        assert!(after.contains("after midnight") && !after.contains("before midnight"));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn hourly_rotation() {
+        let directory = directory("rolling-hourly");
+        let clock = MockClock::new(MIDNIGHT - 1800);
+        let sink = TimeRotatingFileSink::new(&directory, "app.log", Rotation::Hourly).unwrap()
+            .with_clock(clock.clone());
+        write(&sink, "first");
+        clock.set(MIDNIGHT + 1800);
+        write(&sink, "second");
+        assert_eq!(files(&directory), ["app.log.2023-11-04-23", "app.log.2023-11-05-00"]);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn max_age() {
+        let directory = directory("rolling-max-age");
+        for name in ["app.log.2023-11-02", "app.log.2023-11-03", "app.log.notes", "other.2023-11-01"] {
+            fs::write(directory.join(name), "").unwrap();
+        }
+        let clock = MockClock::new(MIDNIGHT - 60);
+        let sink = TimeRotatingFileSink::new(&directory, "app.log", Rotation::Daily).unwrap()
+            .with_max_age(Duration::from_secs(24 * 60 * 60))
+            .with_clock(clock.clone());
+
+        // the file of 2023-11-02 has been complete for more than a day
+        write(&sink, "before midnight");
+        assert_eq!(files(&directory), ["app.log.2023-11-03", "app.log.2023-11-04", "app.log.notes", "other.2023-11-01"]);
+
+        // old files are only deleted when a new file is opened
+        write(&sink, "before midnight");
+        assert_eq!(files(&directory).len(), 4);
+
+        // past midnight, the file of 2023-11-03 has been complete for a day
+        clock.set(MIDNIGHT + 60);
+        write(&sink, "after midnight");
+        assert_eq!(files(&directory), ["app.log.2023-11-04", "app.log.2023-11-05", "app.log.notes", "other.2023-11-01"]);
+        assert!(fs::read_to_string(directory.join("app.log.2023-11-05")).unwrap().contains("after midnight"));
+
+        clock.set(MIDNIGHT + 2 * 24 * 60 * 60 - 60);
+        assert!(sink.cleanup().is_ok());
+        assert_eq!(files(&directory), ["app.log.2023-11-05", "app.log.notes", "other.2023-11-01"]);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
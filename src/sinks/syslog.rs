@@ -0,0 +1,227 @@
+use super::common::{hostname, syslog_severity};
+use super::datetime::{civil_from_days, format_timestamp};
+use crate::{Record, Sink, SinkOutput};
+use std::io;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Syslog facility identifying the type of program logging the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Facility {
+    Kern = 0,
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Lpr = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    AuthPriv = 10,
+    Ftp = 11,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// Format of the messages produced by [`SyslogSink`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyslogFormat {
+    /// [RFC 5424](https://www.rfc-editor.org/rfc/rfc5424) messages.
+    Rfc5424,
+    /// Traditional BSD [RFC 3164](https://www.rfc-editor.org/rfc/rfc3164)
+    /// messages, as produced by the libc `syslog()` function.
+    Rfc3164,
+}
+
+/// A [`Sink`] that sends log messages to the local syslog daemon
+/// over its Unix datagram socket (`/dev/log` by default).
+///
+/// Messages are sent in the [RFC 5424](SyslogFormat::Rfc5424) format
+/// unless configured otherwise using [`SyslogSink::with_format`]. Message
+/// levels are mapped to syslog severities using [`syslog_severity`].
+/// The socket is not connected, so the sink resumes delivery if the
+/// syslog daemon is restarted.
+pub struct SyslogSink {
+    socket : UnixDatagram,
+    path : PathBuf,
+    facility : Facility,
+    format : SyslogFormat,
+    app_name : String,
+    hostname : String,
+    pid : u32,
+}
+
+impl SyslogSink {
+    /// Creates a sink sending messages to `/dev/log` using the
+    /// [`Facility::User`] facility. The app name defaults to the
+    /// name of the current executable.
+    pub fn new() -> io::Result<SyslogSink> {
+        let app_name = std::env::current_exe().ok()
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "-".to_string());
+        let hostname = hostname().unwrap_or_default();
+        Ok(SyslogSink {
+            socket : UnixDatagram::unbound()?,
+            path : PathBuf::from("/dev/log"),
+            facility : Facility::User,
+            format : SyslogFormat::Rfc5424,
+            app_name,
+            hostname,
+            pid : std::process::id(),
+        })
+    }
+
+    /// Sets the path of the syslog socket.
+    pub fn with_path<P : AsRef<Path>>(mut self, path : P) -> Self {
+        self.path = path.as_ref().to_path_buf();
+        self
+    }
+
+    /// Sets the syslog facility.
+    pub fn with_facility(mut self, facility : Facility) -> Self {
+        self.facility = facility;
+        self
+    }
+
+    /// Sets the message format.
+    pub fn with_format(mut self, format : SyslogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Sets the app name (RFC 5424 `APP-NAME`, RFC 3164 `TAG`).
+    pub fn with_app_name(mut self, app_name : &str) -> Self {
+        self.app_name = app_name.to_string();
+        self
+    }
+
+    /// Sets the host name included in RFC 5424 messages.
+    pub fn with_hostname(mut self, hostname : &str) -> Self {
+        self.hostname = hostname.to_string();
+        self
+    }
+
+    /// Returns the path of the syslog socket.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Formats the record as a syslog message.
+    pub fn format_message(&self, record : &Record<'_>) -> String {
+        let priority = (self.facility as u8) * 8 + syslog_severity(record.level);
+        match self.format {
+            SyslogFormat::Rfc5424 => {
+                format!("<{}>1 {} {} {} {} {} - {}",
+                    priority,
+                    format_timestamp(record.timestamp),
+                    header_field(&self.hostname, 255),
+                    header_field(&self.app_name, 48),
+                    self.pid,
+                    header_field(record.target, 32),
+                    record.args)
+            },
+            SyslogFormat::Rfc3164 => {
+                format!("<{}>{} {}[{}]: {}",
+                    priority,
+                    format_bsd_timestamp(record),
+                    self.app_name,
+                    self.pid,
+                    record.args)
+            }
+        }
+    }
+}
+
+impl Sink for SyslogSink {
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        let message = self.format_message(record);
+        self.socket.send_to(message.as_bytes(), &self.path)?;
+        Ok(SinkOutput::PassThrough)
+    }
+}
+
+/// Returns an RFC 5424 header field, i.e. up to `max_len` printable ASCII
+/// characters, or the `-` NILVALUE if the value is empty or invalid.
+fn header_field(value : &str, max_len : usize) -> &str {
+    if value.is_empty() || value.len() > max_len || !value.bytes().all(|b| b.is_ascii_graphic()) {
+        "-"
+    } else {
+        value
+    }
+}
+
+/// Formats the record timestamp in the RFC 3164 `Mmm dd hh:mm:ss` format (UTC).
+fn format_bsd_timestamp(record : &Record<'_>) -> String {
+    const MONTHS : [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    let secs = record.timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (_, month, day) = civil_from_days((secs / 86400) as i64);
+    let secs_of_day = secs % 86400;
+    format!("{} {:>2} {:02}:{:02}:{:02}",
+        MONTHS[month as usize - 1], day,
+        secs_of_day / 3600, secs_of_day % 3600 / 60, secs_of_day % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::{self, directory};
+    use crate::Level;
+
+    fn receive(sink : SyslogSink, record : &Record<'_>) -> String {
+        let path = directory(&format!("syslog-{:?}", sink.format)).join("socket");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        let sink = sink.with_path(&path);
+        assert!(sink.try_write_record(record).is_ok());
+        let mut buffer = [0u8; 1024];
+        let len = socket.recv(&mut buffer).unwrap();
+        String::from_utf8(buffer[..len].to_vec()).unwrap()
+    }
+
+    fn record(args : std::fmt::Arguments<'_>) -> Record<'_> {
+        Record { level : Level::Warn, target : "server", ..testing::record(args) }
+    }
+
+    #[test]
+    fn rfc5424() {
+        let sink = SyslogSink::new().unwrap()
+            .with_facility(Facility::Local3)
+            .with_app_name("app")
+            .with_hostname("host");
+        let message = receive(sink, &record(format_args!("disk {}% full", 95)));
+        assert_eq!(message, format!("<156>1 2023-11-04T09:08:07.250Z host app {} server - disk 95% full", std::process::id()));
+    }
+
+    #[test]
+    fn rfc3164() {
+        let sink = SyslogSink::new().unwrap()
+            .with_format(SyslogFormat::Rfc3164)
+            .with_app_name("app");
+        let message = receive(sink, &record(format_args!("disk {}% full", 95)));
+        assert_eq!(message, format!("<12>Nov  4 09:08:07 app[{}]: disk 95% full", std::process::id()));
+    }
+
+    #[test]
+    fn invalid_header_fields() {
+        let sink = SyslogSink::new().unwrap()
+            .with_app_name("my app")
+            .with_hostname("");
+        let message = sink.format_message(&Record { target : "", ..record(format_args!("message")) });
+        assert!(message.starts_with(&format!("<12>1 2023-11-04T09:08:07.250Z - - {} - - message", std::process::id())), "{}", message);
+    }
+
+    #[test]
+    fn missing_socket() {
+        let path = directory("syslog-missing").join("socket");
+        let sink = SyslogSink::new().unwrap().with_path(path);
+        assert!(sink.try_write_record(&record(format_args!("message"))).is_err());
+        assert_eq!(sink.write_record(&record(format_args!("message"))), SinkOutput::PassThrough);
+    }
+}
@@ -0,0 +1,111 @@
+//!
+//! Fixtures shared by the tests of the logger and the built-in sinks.
+//!
+
+use crate::{Level, LogEntry, Record, Sink, SinkOutput};
+use crate::sinks::Clock;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Serializes tests modifying the global logger state.
+pub fn serial() -> MutexGuard<'static, ()> {
+    static SERIAL : Mutex<()> = Mutex::new(());
+    SERIAL.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// Collects the messages at or above `level` it receives and returns
+/// `output`, or fails to process them if `output` is `None`.
+pub struct CollectSink {
+    output : Option<SinkOutput>,
+    level : Level,
+    entries : Mutex<Vec<LogEntry>>,
+}
+
+impl CollectSink {
+    /// Collects all messages, consuming them if `consume` is set.
+    pub fn new(consume : bool) -> Arc<CollectSink> {
+        CollectSink::with_level(Some(consume.into()), Level::Trace)
+    }
+
+    /// Collects all messages, reporting a failure for each of them.
+    pub fn failing() -> Arc<CollectSink> {
+        CollectSink::with_level(None, Level::Trace)
+    }
+
+    /// Collects all messages, returning `output` for each of them.
+    pub fn with_output(output : SinkOutput) -> Arc<CollectSink> {
+        CollectSink::with_level(Some(output), Level::Trace)
+    }
+
+    pub fn with_level(output : Option<SinkOutput>, level : Level) -> Arc<CollectSink> {
+        Arc::new(CollectSink { output, level, entries : Mutex::new(Vec::new()) })
+    }
+
+    pub fn entries(&self) -> Vec<LogEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    pub fn texts(&self) -> Vec<String> {
+        self.entries().into_iter().map(|entry| entry.text).collect()
+    }
+}
+
+impl Sink for CollectSink {
+    fn try_write_record(&self, record : &Record<'_>) -> io::Result<SinkOutput> {
+        self.entries.lock().unwrap().push(LogEntry::from(record));
+        self.output.clone().ok_or_else(|| io::Error::other("failure"))
+    }
+
+    fn enabled(&self, level : Level) -> bool {
+        level <= self.level
+    }
+}
+
+/// Writes an informational message to the sink.
+pub fn write(sink : &dyn Sink, text : &str) -> SinkOutput {
+    sink.write_record(&Record::new(Level::Info, format_args!("{}", text)))
+}
+
+/// Returns an empty temporary directory for the test `name`.
+pub fn directory(name : &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("workflow-log-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Returns an informational record timestamped 2023-11-04T09:08:07.250Z.
+pub fn record(args : fmt::Arguments<'_>) -> Record<'_> {
+    Record {
+        timestamp : UNIX_EPOCH + Duration::from_millis(1_699_088_887_250),
+        ..Record::new(Level::Info, args)
+    }
+}
+
+/// [`Clock`] returning a time set by the test.
+pub struct MockClock(Mutex<SystemTime>);
+
+impl MockClock {
+    /// Creates a clock set to `secs` seconds after the Unix epoch.
+    pub fn new(secs : u64) -> Arc<MockClock> {
+        Arc::new(MockClock(Mutex::new(UNIX_EPOCH + Duration::from_secs(secs))))
+    }
+
+    pub fn set(&self, secs : u64) {
+        *self.0.lock().unwrap() = UNIX_EPOCH + Duration::from_secs(secs);
+    }
+
+    pub fn advance(&self, duration : Duration) {
+        *self.0.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> SystemTime {
+        *self.0.lock().unwrap()
+    }
+}
@@ -0,0 +1,115 @@
+//!
+//! Selection of the standard stream used for native console output.
+//!
+//! By default [`Level::Error`] and [`Level::Warn`] messages are written
+//! to `stderr` while all other messages are written to `stdout`, so that
+//! diagnostics do not get mixed into the data piped by command line tools.
+//! The stream of each level can be changed using [`set_output_stream`].
+//!
+
+use crate::Level;
+use std::cell::RefCell;
+use std::fmt::{self, Write};
+use std::io::{self, Write as _};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Standard stream receiving the console output of a log level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// `true` for levels written to `stderr`, indexed by [`Level`] - 1.
+static STDERR : [AtomicBool; 5] = [
+    AtomicBool::new(true),
+    AtomicBool::new(true),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+    AtomicBool::new(false),
+];
+
+thread_local! {
+    static BUFFER : RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+#[inline(always)]
+fn index(level : Level) -> usize {
+    level as usize - 1
+}
+
+/// Selects the standard stream receiving console output of the given log level.
+pub fn set_output_stream(level : Level, stream : Stream) {
+    STDERR[index(level)].store(stream == Stream::Stderr, Ordering::Relaxed);
+}
+
+/// Returns the standard stream receiving console output of the given log level.
+pub fn output_stream(level : Level) -> Stream {
+    if STDERR[index(level)].load(Ordering::Relaxed) {
+        Stream::Stderr
+    } else {
+        Stream::Stdout
+    }
+}
+
+/// Writes a line to the stream selected for the given level. The line is
+/// formatted into a per-thread buffer and written using a single write to
+/// the locked stream, so that lines logged by multiple threads do not
+/// interleave.
+pub(crate) fn write_line(level : Level, args : &fmt::Arguments<'_>) {
+    let buffered = BUFFER.try_with(|buffer| match buffer.try_borrow_mut() {
+        Ok(mut buffer) => {
+            buffer.clear();
+            let _ = writeln!(buffer, "{}", args);
+            write_all(level, buffer.as_bytes());
+            // do not retain the memory of exceptionally long messages
+            buffer.shrink_to(1024);
+            true
+        },
+        // formatting `args` has logged another message
+        Err(_) => false
+    });
+    // the buffer is in use or the thread is being destroyed
+    if buffered != Ok(true) {
+        write_all(level, format!("{}\n", args).as_bytes());
+    }
+}
+
+/// Writes `bytes` to the stream selected for the given level. Unlike
+/// `print!()`, failures are ignored, so that logging does not panic
+/// when the stream has been closed (e.g. when piping the output of a
+/// command line tool to `head`).
+fn write_all(level : Level, bytes : &[u8]) {
+    let _ = match output_stream(level) {
+        Stream::Stdout => io::stdout().lock().write_all(bytes),
+        Stream::Stderr => io::stderr().lock().write_all(bytes),
+    };
+}
+
+#[cfg(all(test, feature = "sink"))]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::serial;
+
+    #[test]
+    fn output_streams() {
+        let _serial = serial();
+        assert_eq!(output_stream(Level::Error), Stream::Stderr);
+        assert_eq!(output_stream(Level::Warn), Stream::Stderr);
+        assert_eq!(output_stream(Level::Info), Stream::Stdout);
+        assert_eq!(output_stream(Level::Debug), Stream::Stdout);
+        assert_eq!(output_stream(Level::Trace), Stream::Stdout);
+
+        set_output_stream(Level::Info, Stream::Stderr);
+        set_output_stream(Level::Error, Stream::Stdout);
+        assert_eq!(output_stream(Level::Info), Stream::Stderr);
+        assert_eq!(output_stream(Level::Error), Stream::Stdout);
+        assert_eq!(output_stream(Level::Warn), Stream::Stderr);
+        write_line(Level::Info, &format_args!("written to stderr"));
+
+        set_output_stream(Level::Info, Stream::Stdout);
+        set_output_stream(Level::Error, Stream::Stderr);
+        assert_eq!(output_stream(Level::Info), Stream::Stdout);
+        assert_eq!(output_stream(Level::Error), Stream::Stderr);
+    }
+}
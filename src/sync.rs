@@ -0,0 +1,84 @@
+//!
+//! Poison-tolerant wrappers around [`std::sync`] primitives used
+//! by the logging subsystem.
+//!
+//! A panic on a thread holding one of the logger's locks must not
+//! turn every subsequent log call into a panic, as that would hide
+//! the original failure. The data guarded by these locks remains
+//! consistent at any panic point, so poisoning is cleared and the
+//! lock is used as usual. The first recovery is reported through
+//! the console.
+//!
+
+use crate::{impls, Level};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LockResult, Mutex, MutexGuard};
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Condvar, PoisonError};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+static POISON_REPORTED : AtomicBool = AtomicBool::new(false);
+
+/// Reports the recovery from a poisoned lock on the console, unless a
+/// recovery has been reported before. Returns `true` if it was reported.
+fn report_recovery() -> bool {
+    if POISON_REPORTED.swap(true, Ordering::Relaxed) {
+        return false;
+    }
+    impls::console_impl(Level::Error, &format_args!("workflow-log: recovered from a poisoned lock (a thread panicked while logging)"));
+    true
+}
+
+fn recover<G>(result : LockResult<G>) -> G {
+    result.unwrap_or_else(|err| {
+        report_recovery();
+        err.into_inner()
+    })
+}
+
+/// Locks `mutex`, recovering the guard if the mutex is poisoned.
+pub(crate) fn lock<T>(mutex : &Mutex<T>) -> MutexGuard<'_, T> {
+    recover(mutex.lock())
+}
+
+/// Waits on `condvar`, recovering the guard if the mutex is poisoned.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn wait<'a, T>(condvar : &Condvar, guard : MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+    recover(condvar.wait(guard))
+}
+
+/// Waits on `condvar` for at most `timeout`, recovering the guard if the mutex is poisoned.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn wait_timeout<'a, T>(condvar : &Condvar, guard : MutexGuard<'a, T>, timeout : Duration) -> MutexGuard<'a, T> {
+    match condvar.wait_timeout(guard, timeout) {
+        Ok((guard, _)) => guard,
+        Err(err) => recover::<MutexGuard<'a, T>>(Err(PoisonError::new(err.into_inner().0))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sinks::testing::serial;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[test]
+    fn poisoned_lock() {
+        let _serial = serial();
+        POISON_REPORTED.store(false, Ordering::Relaxed);
+        let mutex = Mutex::new(1);
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            let _guard = lock(&mutex);
+            panic!("failure");
+        }));
+        assert!(result.is_err());
+        assert!(mutex.is_poisoned());
+
+        *lock(&mutex) += 1;
+        assert_eq!(*lock(&mutex), 2);
+        // the recovery has been reported and is not reported again
+        assert!(POISON_REPORTED.load(Ordering::Relaxed));
+        assert!(!report_recovery());
+    }
+}